@@ -9,6 +9,12 @@ fn main() {
     println!("cargo:rerun-if-changed=lean/lakefile.lean");
     println!("cargo:rerun-if-changed=src/lean_wrapper.c");
 
+    // without the lean-model feature the harness checks against the Rust
+    // reference model, and nothing from Lean is built or linked
+    if env::var_os("CARGO_FEATURE_LEAN_MODEL").is_none() {
+        return;
+    }
+
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("manifest dir"));
     let lean_dir = manifest_dir.join("lean");
     let out_dir = PathBuf::from(env::var("OUT_DIR").expect("out dir"));
@@ -0,0 +1,157 @@
+// Reference implementation of the plugin ABI in `src/ledger_sut.h`, wrapping
+// the in-crate `Ledger`:
+//
+//   cargo build --example ledger_plugin
+//   cargo run -- fuzz --sut-lib target/debug/examples/libledger_plugin.so
+//
+// (`.dylib` on macOS.) `tests/plugin.rs` fuzzes it through the plugin path.
+
+use std::os::raw::{c_char, c_void};
+
+use verified_ledger::ledger::Ledger;
+use verified_ledger::op::{ApplyError, Op};
+
+pub struct PluginLedger {
+    ledger: Ledger,
+    // backs the account of the last error until the next call
+    error_account: String,
+}
+
+#[repr(C)]
+pub struct RawError {
+    tag: u8,
+    account: *const c_char,
+    account_len: usize,
+    balance: u64,
+    amount: u64,
+}
+
+unsafe fn account<'a>(account: *const c_char, account_len: usize) -> &'a str {
+    if account_len == 0 {
+        return "";
+    }
+    let bytes = std::slice::from_raw_parts(account as *const u8, account_len);
+    std::str::from_utf8(bytes).expect("account names are UTF-8")
+}
+
+unsafe fn apply(ledger: *mut PluginLedger, op: Op, error: *mut RawError) {
+    let ledger = &mut *ledger;
+    let error = &mut *error;
+    let (tag, account, balance, amount) = match ledger.ledger.apply(&op) {
+        Ok(()) => (0, String::new(), 0, 0),
+        Err(ApplyError::InsufficientFunds {
+            account,
+            balance,
+            amount,
+        }) => (1, account, balance, amount),
+        Err(ApplyError::Overflow {
+            account,
+            balance,
+            amount,
+        }) => (2, account, balance, amount),
+    };
+    ledger.error_account = account;
+    error.tag = tag;
+    error.account = ledger.error_account.as_ptr() as *const c_char;
+    error.account_len = ledger.error_account.len();
+    error.balance = balance;
+    error.amount = amount;
+}
+
+#[no_mangle]
+pub extern "C" fn ledger_sut_new() -> *mut PluginLedger {
+    Box::into_raw(Box::new(PluginLedger {
+        ledger: Ledger::new(),
+        error_account: String::new(),
+    }))
+}
+
+/// # Safety
+/// `ledger` comes from `ledger_sut_new` and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_free(ledger: *mut PluginLedger) {
+    if !ledger.is_null() {
+        drop(Box::from_raw(ledger));
+    }
+}
+
+/// # Safety
+/// As for every function here: `ledger` comes from `ledger_sut_new`, account
+/// names are valid UTF-8 of the given length and `error` is writable.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_apply_deposit(
+    ledger: *mut PluginLedger,
+    account_ptr: *const c_char,
+    account_len: usize,
+    amount: u64,
+    error: *mut RawError,
+) {
+    let account = account(account_ptr, account_len).to_string();
+    apply(ledger, Op::Deposit { account, amount }, error);
+}
+
+/// # Safety
+/// See `ledger_sut_apply_deposit`.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_apply_withdraw(
+    ledger: *mut PluginLedger,
+    account_ptr: *const c_char,
+    account_len: usize,
+    amount: u64,
+    error: *mut RawError,
+) {
+    let account = account(account_ptr, account_len).to_string();
+    apply(ledger, Op::Withdraw { account, amount }, error);
+}
+
+/// # Safety
+/// See `ledger_sut_apply_deposit`.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_apply_transfer(
+    ledger: *mut PluginLedger,
+    from_ptr: *const c_char,
+    from_len: usize,
+    to_ptr: *const c_char,
+    to_len: usize,
+    amount: u64,
+    error: *mut RawError,
+) {
+    let from = account(from_ptr, from_len).to_string();
+    let to = account(to_ptr, to_len).to_string();
+    apply(ledger, Op::Transfer { from, to, amount }, error);
+}
+
+/// # Safety
+/// See `ledger_sut_apply_deposit`.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_balance(
+    ledger: *mut PluginLedger,
+    account_ptr: *const c_char,
+    account_len: usize,
+) -> u64 {
+    let account = account(account_ptr, account_len);
+    (*ledger)
+        .ledger
+        .snapshot()
+        .into_iter()
+        .find(|(name, _)| name == account)
+        .map_or(0, |(_, balance)| balance)
+}
+
+/// # Safety
+/// See `ledger_sut_apply_deposit`; `entry_fn` is called with `ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn ledger_sut_list(
+    ledger: *mut PluginLedger,
+    entry_fn: extern "C" fn(*mut c_void, *const c_char, usize, u64),
+    ctx: *mut c_void,
+) {
+    for (account, balance) in (*ledger).ledger.snapshot() {
+        entry_fn(
+            ctx,
+            account.as_ptr() as *const c_char,
+            account.len(),
+            balance,
+        );
+    }
+}
@@ -0,0 +1,78 @@
+// Reference implementation of the out-of-process protocol in
+// `verified_ledger::sut::process`, wrapping the in-crate `Ledger`:
+//
+//   cargo build --example ledger_stub
+//   cargo run -- fuzz --sut-process target/debug/examples/ledger_stub
+//
+// `--exit-after N` and `--hang-after N` make it exit or stop answering after
+// N requests, to exercise the harness's crash and timeout handling.
+// `--reverse-balances` lists balances in reverse order, as a ledger backed by
+// a hash map might.
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use verified_ledger::ledger::Ledger;
+use verified_ledger::sut::process::{decode_request, encode_reply, Reply, Request};
+
+fn main() {
+    let mut exit_after = None;
+    let mut hang_after = None;
+    let mut reverse_balances = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut count = || {
+            args.next()
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or_else(|| usage(&arg))
+        };
+        match arg.as_str() {
+            "--exit-after" => exit_after = Some(count()),
+            "--hang-after" => hang_after = Some(count()),
+            "--reverse-balances" => reverse_balances = true,
+            _ => usage(&arg),
+        }
+    }
+
+    let mut ledger = Ledger::new();
+    let mut stdout = io::stdout().lock();
+    for (served, line) in io::stdin().lock().lines().enumerate() {
+        let served = served as u64;
+        if exit_after == Some(served) {
+            process::exit(3);
+        }
+        if hang_after == Some(served) {
+            loop {
+                thread::sleep(Duration::from_secs(60));
+            }
+        }
+        let line = line.expect("failed to read stdin");
+        let result = match decode_request(&line) {
+            Ok(Request::Apply(op)) => ledger.apply(&op),
+            Ok(Request::Reset) => {
+                ledger = Ledger::new();
+                Ok(())
+            }
+            Err(message) => {
+                eprintln!("ledger_stub: {}", message);
+                process::exit(2);
+            }
+        };
+        let mut balances = ledger.snapshot();
+        if reverse_balances {
+            balances.reverse();
+        }
+        let reply = Reply { result, balances };
+        writeln!(stdout, "{}", encode_reply(&reply)).expect("failed to write stdout");
+        stdout.flush().expect("failed to write stdout");
+    }
+}
+
+fn usage(arg: &str) -> ! {
+    eprintln!("ledger_stub: unexpected argument {}", arg);
+    eprintln!("usage: ledger_stub [--exit-after N] [--hang-after N] [--reverse-balances]");
+    process::exit(2);
+}
@@ -6,15 +6,27 @@ import VerifiedLedger.Model
 
 namespace VerifiedLedger
 
+-- flattened so the C wrapper can read errors without walking the ApplyError
+-- inductive: `tag` is 0 on success and otherwise the 1-based constructor
+-- index, with the constructor's fields in the `error*` slots
 structure ApplyResult where
-  ok : Bool
   state : State
+  errorAccount : String
+  errorBalance : UInt64
+  errorAmount : UInt64
+  tag : UInt8
   deriving Repr
 
 def applyOp (s : State) (op : Op) : ApplyResult :=
   match apply s op with
-  | .ok s' => { ok := true, state := s' }
-  | .error _ => { ok := false, state := s }
+  | .ok s' =>
+      { state := s', errorAccount := "", errorBalance := 0, errorAmount := 0, tag := 0 }
+  | .error (.insufficientFunds account balance amount) =>
+      { state := s, errorAccount := account, errorBalance := balance, errorAmount := amount,
+        tag := 1 }
+  | .error (.overflow account balance amount) =>
+      { state := s, errorAccount := account, errorBalance := balance, errorAmount := amount,
+        tag := 2 }
 
 @[export verified_ledger_empty]
 def ffiEmpty : State :=
@@ -36,4 +48,9 @@ def ffiApplyTransfer (s : State) (fromAccount : String) (toAccount : String) (am
 def ffiBalance (s : State) (account : String) : UInt64 :=
   balance s account
 
+-- every stored (account, balance) entry, so the harness can compare whole states
+@[export verified_ledger_balances]
+def ffiBalances (s : State) : Array (String × UInt64) :=
+  s.balances.toArray
+
 end VerifiedLedger
@@ -12,6 +12,11 @@ inductive Op where
   | transfer (fromAccount : String) (toAccount : String) (amount : UInt64)
   deriving Repr, DecidableEq
 
+inductive ApplyError where
+  | insufficientFunds (account : String) (balance : UInt64) (amount : UInt64)
+  | overflow (account : String) (balance : UInt64) (amount : UInt64)
+  deriving Repr, DecidableEq
+
 structure State where
   balances : List (String × UInt64)
   deriving Repr
@@ -31,27 +36,37 @@ def setBalance (s : State) (account : String) (amount : UInt64) : State :=
   else
     { balances := (account, amount) :: filtered }
 
-def apply (s : State) (op : Op) : Except String State :=
+-- true when `a + b` does not fit in a UInt64, i.e. the UInt64 sum would wrap
+def addOverflows (a b : UInt64) : Bool :=
+  decide (UInt64.size ≤ a.toNat + b.toNat)
+
+def apply (s : State) (op : Op) : Except ApplyError State :=
   match op with
   | Op.deposit account amount =>
       let current := balance s account
-      Except.ok (setBalance s account (current + amount))
+      if addOverflows current amount then
+        Except.error (ApplyError.overflow account current amount)
+      else
+        Except.ok (setBalance s account (current + amount))
   | Op.withdraw account amount =>
       let current := balance s account
       if current < amount then
-        Except.error s!"insufficient funds: {account}"
+        Except.error (ApplyError.insufficientFunds account current amount)
       else
         Except.ok (setBalance s account (current - amount))
   | Op.transfer fromAccount toAccount amount =>
       let current := balance s fromAccount
       if current < amount then
-        Except.error s!"insufficient funds: {fromAccount}"
+        Except.error (ApplyError.insufficientFunds fromAccount current amount)
+      -- a self-transfer credits back what it debited, so it can never overflow
+      else if fromAccount != toAccount && addOverflows (balance s toAccount) amount then
+        Except.error (ApplyError.overflow toAccount (balance s toAccount) amount)
       else
         let s' := setBalance s fromAccount (current - amount)
         let toBalance := balance s' toAccount
         Except.ok (setBalance s' toAccount (toBalance + amount))
 
-def applyAll (s : State) (ops : List Op) : Except String State :=
+def applyAll (s : State) (ops : List Op) : Except ApplyError State :=
   ops.foldl
     (fun acc op => acc.bind (fun state => apply state op))
     (Except.ok s)
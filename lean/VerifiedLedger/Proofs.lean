@@ -75,20 +75,28 @@ theorem balance_setBalance_other (s : State) (account other : String) (amount :
 
 -- the main theorems
 
-theorem apply_deposit_balance_self (s : State) (account : String) (amount : UInt64) :
+theorem apply_deposit_balance_self (s : State) (account : String) (amount : UInt64)
+    (hover : addOverflows (balance s account) amount = false) :
     (apply s (Op.deposit account amount)).map (fun s' => balance s' account)
       = .ok (balance s account + amount) := by
-  simp [apply, balance_setBalance_self, Except.map]
+  simp [apply, hover, balance_setBalance_self, Except.map]
 
 theorem apply_deposit_balance_other (s : State) (account other : String) (amount : UInt64)
-    (h : other ≠ account) :
+    (hover : addOverflows (balance s account) amount = false) (h : other ≠ account) :
     (apply s (Op.deposit account amount)).map (fun s' => balance s' other)
       = .ok (balance s other) := by
-  simp [apply, balance_setBalance_other, h, Except.map]
+  simp [apply, hover, balance_setBalance_other, h, Except.map]
+
+theorem apply_deposit_overflow (s : State) (account : String) (amount : UInt64)
+    (hover : addOverflows (balance s account) amount = true) :
+    apply s (Op.deposit account amount)
+      = .error (.overflow account (balance s account) amount) := by
+  simp [apply, hover]
 
 theorem apply_withdraw_fail (s : State) (account : String) (amount : UInt64)
     (h : balance s account < amount) :
-    apply s (Op.withdraw account amount) = .error s!"insufficient funds: {account}" := by
+    apply s (Op.withdraw account amount)
+      = .error (.insufficientFunds account (balance s account) amount) := by
   simp [apply, h]
 
 theorem apply_withdraw_ok_balance_self (s : State) (account : String) (amount : UInt64)
@@ -105,17 +113,28 @@ theorem apply_withdraw_ok_balance_other (s : State) (account other : String) (am
 
 theorem apply_transfer_fail (s : State) (fromAccount toAccount : String) (amount : UInt64)
     (h : balance s fromAccount < amount) :
-    apply s (Op.transfer fromAccount toAccount amount) = .error s!"insufficient funds: {fromAccount}" := by
+    apply s (Op.transfer fromAccount toAccount amount)
+      = .error (.insufficientFunds fromAccount (balance s fromAccount) amount) := by
   simp [apply, h]
 
+theorem apply_transfer_overflow (s : State) (fromAccount toAccount : String) (amount : UInt64)
+    (h : ¬ balance s fromAccount < amount) (hneq : fromAccount ≠ toAccount)
+    (hover : addOverflows (balance s toAccount) amount = true) :
+    apply s (Op.transfer fromAccount toAccount amount)
+      = .error (.overflow toAccount (balance s toAccount) amount) := by
+  have hbne : (fromAccount != toAccount) = true := (bne_iff_ne).2 hneq
+  simp [apply, h, hbne, hover]
+
 theorem apply_transfer_ok_balance_from (s : State) (fromAccount toAccount : String) (amount : UInt64)
-    (h : ¬ balance s fromAccount < amount) (hneq : fromAccount ≠ toAccount) :
+    (h : ¬ balance s fromAccount < amount) (hneq : fromAccount ≠ toAccount)
+    (hover : addOverflows (balance s toAccount) amount = false) :
     (apply s (Op.transfer fromAccount toAccount amount)).map (fun s' => balance s' fromAccount)
       = .ok (balance s fromAccount - amount) := by
-  simp [apply, h, balance_setBalance_other, hneq, balance_setBalance_self, Except.map]
+  simp [apply, h, hover, balance_setBalance_other, hneq, balance_setBalance_self, Except.map]
 
 theorem apply_transfer_ok_balance_to (s : State) (fromAccount toAccount : String) (amount : UInt64)
-    (h : ¬ balance s fromAccount < amount) (hneq : fromAccount ≠ toAccount) :
+    (h : ¬ balance s fromAccount < amount) (hneq : fromAccount ≠ toAccount)
+    (hover : addOverflows (balance s toAccount) amount = false) :
     (apply s (Op.transfer fromAccount toAccount amount)).map (fun s' => balance s' toAccount)
       = .ok (balance s toAccount + amount) := by
   have hpre :
@@ -123,10 +142,11 @@ theorem apply_transfer_ok_balance_to (s : State) (fromAccount toAccount : String
         = balance s toAccount := by
     simpa using
       (balance_setBalance_other s fromAccount toAccount (balance s fromAccount - amount) (Ne.symm hneq))
-  simp [apply, h, balance_setBalance_self, hpre, Except.map]
+  simp [apply, h, hover, balance_setBalance_self, hpre, Except.map]
 
 theorem apply_transfer_ok_balance_other (s : State) (fromAccount toAccount other : String)
     (amount : UInt64) (h : ¬ balance s fromAccount < amount)
+    (hover : addOverflows (balance s toAccount) amount = false)
     (hfrom : other ≠ fromAccount) (hto : other ≠ toAccount) :
     (apply s (Op.transfer fromAccount toAccount amount)).map (fun s' => balance s' other)
       = .ok (balance s other) := by
@@ -135,7 +155,7 @@ theorem apply_transfer_ok_balance_other (s : State) (fromAccount toAccount other
         = balance s other := by
     simpa using
       (balance_setBalance_other s fromAccount other (balance s fromAccount - amount) hfrom)
-  simp [apply, h, balance_setBalance_other, hto, hpre, Except.map]
+  simp [apply, h, hover, balance_setBalance_other, hto, hpre, Except.map]
 
 theorem apply_transfer_ok_same_account (s : State) (account : String) (amount : UInt64)
     (h : ¬ balance s account < amount) :
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::fuzz::XorShift64;
+
+/// Where a case's account names come from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Accounts {
+    /// The same names for every case.
+    Fixed(Vec<String>),
+    /// `count` names drawn per case, cycling through `classes`.
+    Generated {
+        classes: Vec<AccountClass>,
+        count: usize,
+    },
+}
+
+impl Default for Accounts {
+    fn default() -> Self {
+        Accounts::Fixed(default_accounts())
+    }
+}
+
+impl fmt::Display for Accounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Accounts::Fixed(names) => write!(f, "{:?}", names),
+            Accounts::Generated { classes, count } => {
+                let classes: Vec<String> = classes.iter().map(|c| c.to_string()).collect();
+                write!(f, "{} from {}", count, classes.join(","))
+            }
+        }
+    }
+}
+
+/// Families of account names, most of them chosen to stress string handling
+/// on either side of the FFI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountClass {
+    /// Short lowercase ASCII.
+    Plain,
+    /// The empty string.
+    Empty,
+    /// Non-ASCII UTF-8, including multi-byte and astral-plane characters.
+    Unicode,
+    /// Several hundred bytes long.
+    Long,
+    /// A precomposed/decomposed pair that only differs in Unicode
+    /// normalization, so the two must stay distinct accounts.
+    Normalization,
+    /// Contains an embedded NUL, which the Lean FFI cannot carry.
+    Nul,
+}
+
+impl AccountClass {
+    pub const ALL: [AccountClass; 6] = [
+        AccountClass::Plain,
+        AccountClass::Empty,
+        AccountClass::Unicode,
+        AccountClass::Long,
+        AccountClass::Normalization,
+        AccountClass::Nul,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            AccountClass::Plain => "plain",
+            AccountClass::Empty => "empty",
+            AccountClass::Unicode => "unicode",
+            AccountClass::Long => "long",
+            AccountClass::Normalization => "normalization",
+            AccountClass::Nul => "nul",
+        }
+    }
+
+    fn generate(self, rng: &mut XorShift64) -> Vec<String> {
+        match self {
+            AccountClass::Plain => vec![random_word(rng, 1, 8)],
+            AccountClass::Empty => vec![String::new()],
+            AccountClass::Unicode => {
+                const CHARS: [char; 8] = ['é', 'ß', 'Ω', 'ж', '日', '本', '🦀', '\u{200b}'];
+                let len = 1 + pick(rng, 4);
+                vec![(0..len).map(|_| CHARS[pick(rng, CHARS.len())]).collect()]
+            }
+            AccountClass::Long => vec![random_word(rng, 256, 1024)],
+            AccountClass::Normalization => {
+                const PAIRS: [(&str, &str); 3] = [
+                    ("caf\u{e9}", "cafe\u{301}"),
+                    ("\u{c5}ngstr\u{f6}m", "A\u{30a}ngstro\u{308}m"),
+                    ("ni\u{f1}o", "nin\u{303}o"),
+                ];
+                let (composed, decomposed) = PAIRS[pick(rng, PAIRS.len())];
+                vec![composed.to_string(), decomposed.to_string()]
+            }
+            AccountClass::Nul => {
+                let head = random_word(rng, 1, 4);
+                let tail = random_word(rng, 0, 4);
+                vec![format!("{}\0{}", head, tail)]
+            }
+        }
+    }
+}
+
+impl fmt::Display for AccountClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AccountClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AccountClass::ALL
+            .into_iter()
+            .find(|class| class.name() == s)
+            .ok_or_else(|| format!("unknown account class: {}", s))
+    }
+}
+
+/// Account names for one case. Generated names never repeat; a class whose
+/// draw collides with an earlier name falls back to a plain name.
+pub fn case_accounts(accounts: &Accounts, rng: &mut XorShift64) -> Vec<String> {
+    let (classes, count) = match accounts {
+        Accounts::Fixed(names) => return names.clone(),
+        Accounts::Generated { classes, count } => (classes, *count),
+    };
+
+    let mut names: Vec<String> = Vec::with_capacity(count);
+    let mut class_index = 0;
+    while names.len() < count && !classes.is_empty() {
+        let class = classes[class_index % classes.len()];
+        class_index += 1;
+        for name in class.generate(rng) {
+            let name = if names.contains(&name) {
+                unique_plain(rng, &names)
+            } else {
+                name
+            };
+            if names.len() < count {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Invents an account name not in `taken`, from the same classes `accounts`
+/// generates (plain names for a fixed universe).
+pub fn fresh_account(accounts: &Accounts, taken: &[String], rng: &mut XorShift64) -> String {
+    let class = match accounts {
+        Accounts::Fixed(_) => AccountClass::Plain,
+        Accounts::Generated { classes, .. } => classes[pick(rng, classes.len())],
+    };
+    class
+        .generate(rng)
+        .into_iter()
+        .find(|name| !taken.contains(name))
+        .unwrap_or_else(|| unique_plain(rng, taken))
+}
+
+pub fn default_accounts() -> Vec<String> {
+    vec![
+        "alice".to_string(),
+        "bob".to_string(),
+        "carol".to_string(),
+        "dave".to_string(),
+        "erin".to_string(),
+    ]
+}
+
+fn unique_plain(rng: &mut XorShift64, taken: &[String]) -> String {
+    loop {
+        let name = random_word(rng, 1, 8);
+        if !taken.contains(&name) {
+            return name;
+        }
+    }
+}
+
+// lowercase ASCII, between `min` and `max` letters long
+fn random_word(rng: &mut XorShift64, min: usize, max: usize) -> String {
+    let len = min + pick(rng, max - min + 1);
+    (0..len)
+        .map(|_| (b'a' + pick(rng, 26) as u8) as char)
+        .collect()
+}
+
+fn pick(rng: &mut XorShift64, bound: usize) -> usize {
+    (rng.next_u64() % bound as u64) as usize
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+
+use crate::fuzz::StepRecord;
+use crate::op::Op;
+
+/// The partitions steps are sorted into: one per `Proofs.lean` theorem whose
+/// hypotheses a step satisfies, with the boundaries worth hitting on their
+/// own (an exact-balance withdraw, a sum of exactly `u64::MAX`) split out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    DepositSelf,
+    DepositSelfAtMax,
+    DepositOther,
+    DepositOverflow,
+    WithdrawFail,
+    WithdrawSelf,
+    WithdrawSelfExact,
+    WithdrawOther,
+    TransferFail,
+    TransferFailSameAccount,
+    TransferOverflow,
+    TransferFromTo,
+    TransferFromToExact,
+    TransferOther,
+    TransferSameAccount,
+}
+
+impl Class {
+    pub const ALL: [Class; 15] = [
+        Class::DepositSelf,
+        Class::DepositSelfAtMax,
+        Class::DepositOther,
+        Class::DepositOverflow,
+        Class::WithdrawFail,
+        Class::WithdrawSelf,
+        Class::WithdrawSelfExact,
+        Class::WithdrawOther,
+        Class::TransferFail,
+        Class::TransferFailSameAccount,
+        Class::TransferOverflow,
+        Class::TransferFromTo,
+        Class::TransferFromToExact,
+        Class::TransferOther,
+        Class::TransferSameAccount,
+    ];
+
+    pub fn theorem(self) -> &'static str {
+        match self {
+            Class::DepositSelf | Class::DepositSelfAtMax => "apply_deposit_balance_self",
+            Class::DepositOther => "apply_deposit_balance_other",
+            Class::DepositOverflow => "apply_deposit_overflow",
+            Class::WithdrawFail => "apply_withdraw_fail",
+            Class::WithdrawSelf | Class::WithdrawSelfExact => "apply_withdraw_ok_balance_self",
+            Class::WithdrawOther => "apply_withdraw_ok_balance_other",
+            Class::TransferFail | Class::TransferFailSameAccount => "apply_transfer_fail",
+            Class::TransferOverflow => "apply_transfer_overflow",
+            Class::TransferFromTo | Class::TransferFromToExact => {
+                "apply_transfer_ok_balance_from/_to"
+            }
+            Class::TransferOther => "apply_transfer_ok_balance_other",
+            Class::TransferSameAccount => "apply_transfer_ok_same_account",
+        }
+    }
+
+    /// The hypotheses that put a step in this class, in terms of the model's
+    /// state just before it.
+    pub fn condition(self) -> &'static str {
+        match self {
+            Class::DepositSelf => "balance + amount < max",
+            Class::DepositSelfAtMax => "balance + amount = max",
+            Class::DepositOther => "no overflow, another account held",
+            Class::DepositOverflow => "balance + amount > max",
+            Class::WithdrawFail => "balance < amount",
+            Class::WithdrawSelf => "balance > amount",
+            Class::WithdrawSelfExact => "balance = amount",
+            Class::WithdrawOther => "balance >= amount, another account held",
+            Class::TransferFail => "from balance < amount, from != to",
+            Class::TransferFailSameAccount => "from balance < amount, from = to",
+            Class::TransferOverflow => "from != to, to balance + amount > max",
+            Class::TransferFromTo => "from balance > amount, no overflow",
+            Class::TransferFromToExact => "from balance = amount, no overflow",
+            Class::TransferOther => "transfer ok, a third account held",
+            Class::TransferSameAccount => "from = to, balance >= amount",
+        }
+    }
+
+    /// The kind of op that can land in this class, as in `OpWeights::KEYS`.
+    pub fn op(self) -> &'static str {
+        match self {
+            Class::DepositSelf
+            | Class::DepositSelfAtMax
+            | Class::DepositOther
+            | Class::DepositOverflow => "deposit",
+            Class::WithdrawFail
+            | Class::WithdrawSelf
+            | Class::WithdrawSelfExact
+            | Class::WithdrawOther => "withdraw",
+            _ => "transfer",
+        }
+    }
+
+    fn index(self) -> usize {
+        Class::ALL
+            .iter()
+            .position(|class| *class == self)
+            .expect("every class is in ALL")
+    }
+
+    /// Every class `op` falls into when applied to `pre`. Ok steps land in
+    /// the theorem about the touched account and, when some other account
+    /// holds a balance, the one about untouched accounts too.
+    pub fn of(pre: &[(String, u64)], op: &Op) -> Vec<Class> {
+        let balance = |account: &str| {
+            pre.iter()
+                .find(|(name, _)| name == account)
+                .map_or(0, |(_, balance)| *balance)
+        };
+        let other_held = |touched: &[&str]| {
+            pre.iter()
+                .any(|(name, balance)| *balance > 0 && !touched.contains(&name.as_str()))
+        };
+
+        let mut classes = Vec::with_capacity(2);
+        match op {
+            Op::Deposit { account, amount } => match balance(account).checked_add(*amount) {
+                None => classes.push(Class::DepositOverflow),
+                Some(sum) => {
+                    classes.push(if sum == u64::MAX {
+                        Class::DepositSelfAtMax
+                    } else {
+                        Class::DepositSelf
+                    });
+                    if other_held(&[account]) {
+                        classes.push(Class::DepositOther);
+                    }
+                }
+            },
+            Op::Withdraw { account, amount } => {
+                let current = balance(account);
+                if current < *amount {
+                    classes.push(Class::WithdrawFail);
+                } else {
+                    classes.push(if current == *amount {
+                        Class::WithdrawSelfExact
+                    } else {
+                        Class::WithdrawSelf
+                    });
+                    if other_held(&[account]) {
+                        classes.push(Class::WithdrawOther);
+                    }
+                }
+            }
+            Op::Transfer { from, to, amount } => {
+                let current = balance(from);
+                if current < *amount {
+                    classes.push(if from == to {
+                        Class::TransferFailSameAccount
+                    } else {
+                        Class::TransferFail
+                    });
+                } else if from == to {
+                    classes.push(Class::TransferSameAccount);
+                } else if balance(to).checked_add(*amount).is_none() {
+                    classes.push(Class::TransferOverflow);
+                } else {
+                    classes.push(if current == *amount {
+                        Class::TransferFromToExact
+                    } else {
+                        Class::TransferFromTo
+                    });
+                    if other_held(&[from, to]) {
+                        classes.push(Class::TransferOther);
+                    }
+                }
+            }
+        }
+        classes
+    }
+}
+
+/// Steps seen per class.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    counts: [u64; Class::ALL.len()],
+}
+
+impl Coverage {
+    pub fn record(&mut self, pre: &[(String, u64)], op: &Op) {
+        for class in Class::of(pre, op) {
+            self.counts[class.index()] += 1;
+        }
+    }
+
+    /// Records every step of `history`, starting from the `initial` balances
+    /// it was seeded with.
+    pub fn record_history(&mut self, initial: &[(String, u64)], history: &[StepRecord]) {
+        let mut pre = initial;
+        for step in history {
+            self.record(pre, &step.op);
+            pre = &step.model_snapshot;
+        }
+    }
+
+    pub fn add(&mut self, other: &Coverage) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn count(&self, class: Class) -> u64 {
+        self.counts[class.index()]
+    }
+
+    pub fn missed(&self) -> impl Iterator<Item = Class> + '_ {
+        Class::ALL
+            .into_iter()
+            .filter(|class| self.count(*class) == 0)
+    }
+}
+
+/// The per-class table printed at the end of a run.
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\nspec coverage (steps per Proofs.lean theorem):")?;
+        for class in Class::ALL {
+            let count = self.count(class);
+            write!(
+                f,
+                "  {:<36} {:<40} {:>10}",
+                class.theorem(),
+                class.condition(),
+                count
+            )?;
+            if count == 0 {
+                write!(f, "  never exercised")?;
+            }
+            writeln!(f)?;
+        }
+        let missed = self.missed().count();
+        if missed > 0 {
+            writeln!(
+                f,
+                "{} of {} classes never exercised",
+                missed,
+                Class::ALL.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balances(pairs: &[(&str, u64)]) -> Vec<(String, u64)> {
+        pairs
+            .iter()
+            .map(|(account, balance)| (account.to_string(), *balance))
+            .collect()
+    }
+
+    fn deposit(account: &str, amount: u64) -> Op {
+        Op::Deposit {
+            account: account.to_string(),
+            amount,
+        }
+    }
+
+    fn withdraw(account: &str, amount: u64) -> Op {
+        Op::Withdraw {
+            account: account.to_string(),
+            amount,
+        }
+    }
+
+    fn transfer(from: &str, to: &str, amount: u64) -> Op {
+        Op::Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn every_class_has_a_step_that_lands_in_it() {
+        let alone = balances(&[("a", 10)]);
+        let pair = balances(&[("a", 10), ("b", 5)]);
+        let all = balances(&[("a", 10), ("b", 5), ("full", u64::MAX)]);
+        let table = [
+            (&alone, deposit("a", 3), vec![Class::DepositSelf]),
+            (
+                &alone,
+                deposit("new", 0),
+                vec![Class::DepositSelf, Class::DepositOther],
+            ),
+            (
+                &alone,
+                deposit("a", u64::MAX - 10),
+                vec![Class::DepositSelfAtMax],
+            ),
+            (
+                &all,
+                deposit("a", 3),
+                vec![Class::DepositSelf, Class::DepositOther],
+            ),
+            (&all, deposit("full", 1), vec![Class::DepositOverflow]),
+            (&alone, deposit("a", u64::MAX), vec![Class::DepositOverflow]),
+            (&alone, withdraw("a", 11), vec![Class::WithdrawFail]),
+            (&all, withdraw("new", 1), vec![Class::WithdrawFail]),
+            (&alone, withdraw("a", 4), vec![Class::WithdrawSelf]),
+            (&alone, withdraw("a", 10), vec![Class::WithdrawSelfExact]),
+            (
+                &all,
+                withdraw("a", 4),
+                vec![Class::WithdrawSelf, Class::WithdrawOther],
+            ),
+            (
+                &all,
+                withdraw("a", 10),
+                vec![Class::WithdrawSelfExact, Class::WithdrawOther],
+            ),
+            (&all, transfer("a", "b", 11), vec![Class::TransferFail]),
+            (
+                &all,
+                transfer("a", "a", 11),
+                vec![Class::TransferFailSameAccount],
+            ),
+            (
+                &all,
+                transfer("a", "full", 1),
+                vec![Class::TransferOverflow],
+            ),
+            (&pair, transfer("a", "b", 4), vec![Class::TransferFromTo]),
+            (
+                &pair,
+                transfer("a", "b", 10),
+                vec![Class::TransferFromToExact],
+            ),
+            (
+                &pair,
+                transfer("a", "new", 0),
+                vec![Class::TransferFromTo, Class::TransferOther],
+            ),
+            (
+                &all,
+                transfer("a", "b", 4),
+                vec![Class::TransferFromTo, Class::TransferOther],
+            ),
+            (
+                &all,
+                transfer("a", "a", 10),
+                vec![Class::TransferSameAccount],
+            ),
+            (
+                &alone,
+                transfer("a", "a", 0),
+                vec![Class::TransferSameAccount],
+            ),
+        ];
+
+        let mut coverage = Coverage::default();
+        for (pre, op, expected) in &table {
+            assert_eq!(Class::of(pre, op), *expected, "{} from {:?}", op, pre);
+            coverage.record(pre, op);
+        }
+        assert_eq!(coverage.missed().collect::<Vec<_>>(), []);
+        // each class only ever holds ops of the kind it names
+        for (_, op, expected) in &table {
+            for class in expected {
+                assert!(
+                    op.to_string().starts_with(class.op()),
+                    "{:?} from {}",
+                    class,
+                    op
+                );
+            }
+        }
+    }
+}
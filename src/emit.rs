@@ -0,0 +1,319 @@
+use crate::fuzz::{self, FuzzFailure, StepRecord};
+use crate::op::{ApplyError, Op};
+use crate::sut::LedgerUnderTest;
+
+/// Renders `failure` as a self-contained Rust test module that replays it
+/// against `Ledger::new()`.
+///
+/// The minimized trace is used when there is one. Every step asserts the Lean
+/// model's result and full snapshot, written out as literals, so the test
+/// needs neither the model nor the fuzzer to run and keeps failing until the
+/// implementation agrees with the model again.
+pub fn rust_test(failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> String {
+    let (initial, steps) = expected_steps(failure, sut);
+    let name = format!("seed_{}_case_{}", failure.seed, failure.case_index);
+
+    let mut out = String::new();
+    out.push_str("// Regression test generated by `verified-ledger` from a divergence between\n");
+    out.push_str(&format!(
+        "// the Lean model and `Ledger` (seed={}, case={}, diverging op: {}).\n",
+        failure.seed, failure.case_index, failure.op
+    ));
+    out.push_str("// Expected results and balances were recorded from the Lean model.\n");
+    out.push_str(&format!(
+        "// Save as src/fuzz_regression_{}.rs and add `mod fuzz_regression_{};` to src/lib.rs.\n",
+        name, name
+    ));
+    out.push_str(
+        "#![cfg(test)]
+
+use crate::ledger::Ledger;
+#[allow(unused_imports)]
+use crate::op::{ApplyError, Op};
+
+fn balances(entries: &[(&str, u64)]) -> Vec<(String, u64)> {
+    entries
+        .iter()
+        .map(|(account, balance)| (account.to_string(), *balance))
+        .collect()
+}
+
+#[test]
+",
+    );
+    out.push_str(&format!("fn {}() {{\n", name));
+    out.push_str("    let mut ledger = Ledger::new();\n");
+    for (account, balance) in &initial {
+        let op = Op::Deposit {
+            account: account.clone(),
+            amount: *balance,
+        };
+        out.push_str(&format!(
+            "    ledger.apply(&{}).expect(\"seeding deposit\");\n",
+            op_literal(&op)
+        ));
+    }
+
+    for (index, step) in steps.iter().enumerate() {
+        out.push_str(&format!("\n    // step {}: {}\n", index, step.op));
+        out.push_str(&format!("    let op = {};\n", op_literal(&step.op)));
+        out.push_str(&format!(
+            "    assert_eq!(ledger.apply(&op), {}, \"step {}: {{}}\", op);\n",
+            result_literal(&step.model_result),
+            index
+        ));
+        out.push_str(&format!(
+            "    assert_eq!(ledger.snapshot(), balances(&{}), \"step {}: {{}}\", op);\n",
+            balances_literal(&step.model_snapshot),
+            index
+        ));
+    }
+    out.push_str("}\n");
+
+    out
+}
+
+// the trace a regression should pin down (the minimized one when shrinking
+// ran, replayed against `sut` to find where it diverges) with the model's
+// verdict and state after each of its steps
+fn expected_steps(
+    failure: &FuzzFailure,
+    sut: &mut dyn LedgerUnderTest,
+) -> (Vec<(String, u64)>, Vec<StepRecord>) {
+    if let Some(minimized) = &failure.minimized {
+        if let Err(replayed) = fuzz::replay_trace(minimized, failure.seed, failure.case_index, sut)
+        {
+            return (replayed.initial, replayed.history);
+        }
+    }
+    (failure.initial.clone(), failure.history.clone())
+}
+
+// account names go through `{:?}`, which always yields a valid Rust literal
+fn op_literal(op: &Op) -> String {
+    match op {
+        Op::Deposit { account, amount } => format!(
+            "Op::Deposit {{ account: {:?}.to_string(), amount: {} }}",
+            account, amount
+        ),
+        Op::Withdraw { account, amount } => format!(
+            "Op::Withdraw {{ account: {:?}.to_string(), amount: {} }}",
+            account, amount
+        ),
+        Op::Transfer { from, to, amount } => format!(
+            "Op::Transfer {{ from: {:?}.to_string(), to: {:?}.to_string(), amount: {} }}",
+            from, to, amount
+        ),
+    }
+}
+
+fn result_literal(result: &Result<(), ApplyError>) -> String {
+    let (variant, account, balance, amount) = match result {
+        Ok(()) => return "Ok(())".to_string(),
+        Err(ApplyError::InsufficientFunds {
+            account,
+            balance,
+            amount,
+        }) => ("InsufficientFunds", account, balance, amount),
+        Err(ApplyError::Overflow {
+            account,
+            balance,
+            amount,
+        }) => ("Overflow", account, balance, amount),
+    };
+    format!(
+        "Err(ApplyError::{} {{ account: {:?}.to_string(), balance: {}, amount: {} }})",
+        variant, account, balance, amount
+    )
+}
+
+fn balances_literal(balances: &[(String, u64)]) -> String {
+    let entries: Vec<String> = balances
+        .iter()
+        .map(|(account, balance)| format!("({:?}, {})", account, balance))
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+/// Renders `failure` as a Lean file that checks the model's side of it
+/// without going through the FFI.
+///
+/// The trace (minimized when there is one) is split into the ops the model
+/// accepted, which `applyAll` runs on `empty` after the initial-balance
+/// deposits, and the ops it rejected, each of which is re-applied to the state
+/// just before it. `#eval` checks fail the Lean build if the model's final
+/// balances or any of its errors differ from what the harness recorded.
+pub fn lean_checks(failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> String {
+    let (initial, steps) = expected_steps(failure, sut);
+    let name = format!("Seed{}Case{}", failure.seed, failure.case_index);
+
+    let mut out = String::new();
+    out.push_str("-- Generated by `verified-ledger` from a divergence between the Lean model\n");
+    out.push_str(&format!(
+        "-- and `Ledger` (seed={}, case={}, diverging op: {}).\n",
+        failure.seed, failure.case_index, failure.op
+    ));
+    out.push_str("-- Expected results and balances were recorded from the model over the FFI.\n");
+    out.push_str(&format!(
+        "-- Save as lean/VerifiedLedger/Regressions/{}.lean and run\n",
+        name
+    ));
+    out.push_str(&format!(
+        "-- `lake build VerifiedLedger.Regressions.{}` from lean/.\n",
+        name
+    ));
+    out.push_str(&format!(
+        "
+import VerifiedLedger.Model
+
+namespace VerifiedLedger.Regressions.{}
+
+-- `ops` leaves the model in exactly the `expected` balances
+def endsWith (ops : List Op) (expected : List (String × UInt64)) : Bool :=
+  match applyAll empty ops with
+  | .ok s =>
+      s.balances.length == expected.length &&
+        expected.all (fun entry => balance s entry.1 == entry.2)
+  | .error _ => false
+
+def errorOf : Except ApplyError State → Option ApplyError
+  | .error e => some e
+  | .ok _ => none
+
+-- after `pre`, the model rejects `op` with exactly `expected`
+def rejects (pre : List Op) (op : Op) (expected : ApplyError) : Bool :=
+  match applyAll empty pre with
+  | .ok s => errorOf (apply s op) == some expected
+  | .error _ => false
+
+def check (label : String) (ok : Bool) : IO Unit :=
+  if ok then pure () else throw (IO.userError s!\"{{label}}: model disagrees with the recorded verdict\")
+
+",
+        name
+    ));
+
+    let seed: Vec<String> = initial
+        .iter()
+        .map(|(account, balance)| {
+            lean_op(&Op::Deposit {
+                account: account.clone(),
+                amount: *balance,
+            })
+        })
+        .collect();
+    out.push_str("-- initial balances\n");
+    out.push_str(&format!("def seed : List Op := {}\n\n", lean_list(&seed)));
+
+    let accepted: Vec<String> = steps
+        .iter()
+        .filter(|step| step.model_result.is_ok())
+        .map(|step| lean_op(&step.op))
+        .collect();
+    out.push_str("-- the ops the model accepted, in order\n");
+    out.push_str(&format!(
+        "def ops : List Op := {}\n\n",
+        lean_list(&accepted)
+    ));
+
+    let expected = steps.last().map_or(&initial, |step| &step.model_snapshot);
+    let expected: Vec<String> = expected
+        .iter()
+        .map(|(account, balance)| format!("({}, {})", lean_string(account), balance))
+        .collect();
+    out.push_str(&format!(
+        "def expected : List (String × UInt64) := {}\n\n",
+        lean_list(&expected)
+    ));
+    out.push_str("#eval check \"final balances\" (endsWith (seed ++ ops) expected)\n");
+
+    let mut accepted_before = 0;
+    for (index, step) in steps.iter().enumerate() {
+        let error = match &step.model_result {
+            Ok(()) => {
+                accepted_before += 1;
+                continue;
+            }
+            Err(error) => error,
+        };
+        let label = lean_string(&format!("step {}: {}", index, step.op));
+        out.push_str(&format!(
+            "#eval check {} (rejects (seed ++ ops.take {}) ({}) ({}))\n",
+            label,
+            accepted_before,
+            lean_op(&step.op),
+            lean_error(error)
+        ));
+    }
+
+    out.push_str(&format!("\nend VerifiedLedger.Regressions.{}\n", name));
+    out
+}
+
+fn lean_op(op: &Op) -> String {
+    match op {
+        Op::Deposit { account, amount } => {
+            format!(".deposit {} {}", lean_string(account), amount)
+        }
+        Op::Withdraw { account, amount } => {
+            format!(".withdraw {} {}", lean_string(account), amount)
+        }
+        Op::Transfer { from, to, amount } => format!(
+            ".transfer {} {} {}",
+            lean_string(from),
+            lean_string(to),
+            amount
+        ),
+    }
+}
+
+fn lean_error(error: &ApplyError) -> String {
+    let (constructor, account, balance, amount) = match error {
+        ApplyError::InsufficientFunds {
+            account,
+            balance,
+            amount,
+        } => ("insufficientFunds", account, balance, amount),
+        ApplyError::Overflow {
+            account,
+            balance,
+            amount,
+        } => ("overflow", account, balance, amount),
+    };
+    format!(
+        ".{} {} {} {}",
+        constructor,
+        lean_string(account),
+        balance,
+        amount
+    )
+}
+
+fn lean_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return "[]".to_string();
+    }
+    format!("[\n  {}\n]", items.join(",\n  "))
+}
+
+// Lean string literals only know \\ \" \n \t \r, \xHH and four-digit \uHHHH.
+// non-ASCII BMP characters are escaped so combining marks and zero-width
+// characters stay visible; astral characters have no escape and go in as-is
+fn lean_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c if c.is_ascii() => out.push(c),
+            c if (c as u32) <= 0xffff => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
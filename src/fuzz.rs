@@ -1,9 +1,20 @@
+use std::cell::{Cell, RefCell};
 use std::fmt;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::lean_model::LeanLedger;
-use crate::ledger::Ledger;
+use crate::accounts::{self, Accounts};
+use crate::coverage::{Class, Coverage};
+use crate::lean_model::{self, LeanLedger, LeanThread};
 use crate::op::{ApplyError, Op};
+use crate::reference::ReferenceModel;
+use crate::shrink;
+use crate::sut::{Implementation, LedgerUnderTest};
+use crate::triage::{self, Bucket};
 
 #[derive(Clone, Debug)]
 pub struct FuzzConfig {
@@ -11,6 +22,25 @@ pub struct FuzzConfig {
     pub cases: u64,
     pub steps: usize,
     pub max_amount: u64,
+    pub accounts: Accounts,
+    /// Percent chance that an op picks a newly invented account instead of
+    /// one it has seen before.
+    pub fresh_accounts: u32,
+    pub op_weights: OpWeights,
+    pub amount_mix: AmountMix,
+    pub swarm: bool,
+    /// Aim about half the ops at the spec partitions the case has hit least.
+    pub directed: bool,
+    /// The ledger checked against the model.
+    pub implementation: Implementation,
+    pub shrink: bool,
+    /// Worker threads cases are spread over.
+    pub jobs: usize,
+    /// Stop starting new cases once this much time has passed.
+    pub duration: Option<Duration>,
+    /// Run until stopped from outside (within `cases` and `duration`, which
+    /// soak runs normally leave unbounded).
+    pub soak: bool,
 }
 
 impl FuzzConfig {
@@ -20,8 +50,195 @@ impl FuzzConfig {
             cases: 50,
             steps: 200,
             max_amount: 50,
+            accounts: Accounts::default(),
+            fresh_accounts: 2,
+            op_weights: OpWeights::default(),
+            amount_mix: AmountMix::default(),
+            swarm: false,
+            directed: false,
+            implementation: Implementation::default(),
+            shrink: true,
+            jobs: 1,
+            duration: None,
+            soak: false,
         }
     }
+
+    /// Runs bounded by time or by an outside stop rather than by `cases`;
+    /// these keep totals instead of every case.
+    pub fn is_campaign(&self) -> bool {
+        self.duration.is_some() || self.soak
+    }
+
+    /// Flags for `replay` that regenerate the case seeded with `seed` up to
+    /// `steps` ops, quoted for a POSIX shell. The amount bound is always
+    /// given; everything else only when it differs from the default.
+    /// `--accounts` cannot carry names with a comma or NUL, which only the
+    /// library API can set; those cases replay from their trace file.
+    pub fn replay_args(&self, seed: u64, steps: usize) -> String {
+        let defaults = FuzzConfig::new(seed);
+        let mut args = format!(
+            "--seed {} --steps {} --max-amount {}",
+            seed, steps, self.max_amount
+        );
+        match &self.accounts {
+            accounts if *accounts == defaults.accounts => {}
+            Accounts::Fixed(names) => {
+                args.push_str(&format!(" --accounts {}", shell_quote(&names.join(","))))
+            }
+            Accounts::Generated { classes, count } => {
+                let classes: Vec<String> = classes.iter().map(|c| c.to_string()).collect();
+                args.push_str(&format!(
+                    " --account-classes {} --account-count {}",
+                    classes.join(","),
+                    count
+                ));
+            }
+        }
+        if self.fresh_accounts != defaults.fresh_accounts {
+            args.push_str(&format!(" --fresh-accounts {}", self.fresh_accounts));
+        }
+        if self.op_weights != defaults.op_weights {
+            args.push_str(&format!(" --op-weights {}", self.op_weights));
+        }
+        if self.amount_mix != defaults.amount_mix {
+            args.push_str(&format!(" --amount-mix {}", self.amount_mix));
+        }
+        if self.swarm {
+            args.push_str(" --swarm");
+        }
+        if self.directed {
+            args.push_str(" --directed");
+        }
+        if !self.implementation.replay_args().is_empty() {
+            args.push(' ');
+            args.push_str(self.implementation.replay_args());
+        }
+        args
+    }
+}
+
+// `text` as one shell word: left bare when nothing in it is special, else in
+// single quotes, inside which only `'` itself needs escaping
+fn shell_quote(text: &str) -> String {
+    let bare = |c: char| c.is_ascii_alphanumeric() || "-_.,/:=+@%".contains(c);
+    if !text.is_empty() && text.chars().all(bare) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Relative weights of the op kinds `random_op` generates. If they are all 0,
+/// it generates deposits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpWeights {
+    pub deposit: u32,
+    pub withdraw: u32,
+    pub transfer: u32,
+}
+
+impl OpWeights {
+    pub const KEYS: [&'static str; 3] = ["deposit", "withdraw", "transfer"];
+
+    pub fn from_weights(weights: [u32; 3]) -> Self {
+        let [deposit, withdraw, transfer] = weights;
+        Self {
+            deposit,
+            withdraw,
+            transfer,
+        }
+    }
+
+    pub fn weights(&self) -> [u32; 3] {
+        [self.deposit, self.withdraw, self.transfer]
+    }
+}
+
+impl Default for OpWeights {
+    fn default() -> Self {
+        Self::from_weights([45, 30, 25])
+    }
+}
+
+impl fmt::Display for OpWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = Self::KEYS
+            .iter()
+            .zip(self.weights())
+            .map(|(key, weight)| format!("{}={}", key, weight))
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Relative weights of the strategies `random_op` draws amounts from. The
+/// balance-relative strategies look at the model's current balance of the
+/// account being debited (or credited, for deposits).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmountMix {
+    /// Uniform in `1..=max_amount`.
+    pub uniform: u32,
+    /// Exactly the current balance.
+    pub balance: u32,
+    /// The current balance plus or minus one.
+    pub near_balance: u32,
+    pub zero: u32,
+    /// `u64::MAX`.
+    pub max: u32,
+    /// `u64::MAX - balance` of the credited account, the largest amount that
+    /// does not overflow, or one more than that.
+    pub complement: u32,
+}
+
+impl AmountMix {
+    pub const KEYS: [&'static str; 6] = [
+        "uniform",
+        "balance",
+        "near-balance",
+        "zero",
+        "max",
+        "complement",
+    ];
+
+    pub fn from_weights(weights: [u32; 6]) -> Self {
+        let [uniform, balance, near_balance, zero, max, complement] = weights;
+        Self {
+            uniform,
+            balance,
+            near_balance,
+            zero,
+            max,
+            complement,
+        }
+    }
+
+    pub fn weights(&self) -> [u32; 6] {
+        [
+            self.uniform,
+            self.balance,
+            self.near_balance,
+            self.zero,
+            self.max,
+            self.complement,
+        ]
+    }
+}
+
+impl Default for AmountMix {
+    fn default() -> Self {
+        Self::from_weights([60, 10, 10, 10, 5, 5])
+    }
+}
+
+impl fmt::Display for AmountMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = Self::KEYS
+            .iter()
+            .zip(self.weights())
+            .map(|(key, weight)| format!("{}={}", key, weight))
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -31,18 +248,88 @@ pub struct FuzzFailure {
     pub step_index: usize,
     pub op: Op,
     pub model_result: Result<(), ApplyError>,
-    pub impl_result: Result<(), ApplyError>,
+    pub impl_outcome: ImplOutcome,
     pub model_pre_snapshot: Vec<(String, u64)>,
     pub impl_pre_snapshot: Vec<(String, u64)>,
+    pub initial: Vec<(String, u64)>,
     pub history: Vec<StepRecord>,
+    pub minimized: Option<Trace>,
+    /// Arguments to `replay` that reproduce this failure.
+    pub replay_args: String,
+    /// Set when the Lean model and the Rust reference disagreed on this
+    /// step, which is a fault in the harness rather than the implementation.
+    /// Never set without the `lean-model` feature, where the model is the
+    /// reference.
+    pub harness_fault: Option<HarnessFault>,
+}
+
+/// The Rust reference's side of a step the Lean model disagreed with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HarnessFault {
+    pub reference_result: Result<(), ApplyError>,
+    pub reference_snapshot: Vec<(String, u64)>,
+}
+
+/// What the implementation did with one op: its verdict, or a panic raised
+/// while applying it (which always counts as a divergence).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImplOutcome {
+    Returned(Result<(), ApplyError>),
+    Panicked(String),
+}
+
+impl ImplOutcome {
+    pub fn matches(&self, model_result: &Result<(), ApplyError>) -> bool {
+        match self {
+            ImplOutcome::Returned(result) => result == model_result,
+            ImplOutcome::Panicked(_) => false,
+        }
+    }
+}
+
+/// Initial balances plus the ops applied on top of them; enough to replay a
+/// case against fresh model and implementation instances.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    pub initial: Vec<(String, u64)>,
+    pub ops: Vec<Op>,
+}
+
+impl Trace {
+    /// Every account the trace touches, in order of first use by an op.
+    pub fn accounts(&self) -> Vec<String> {
+        let mut accounts: Vec<String> = Vec::new();
+        let mut add = |account: &String| {
+            if !accounts.contains(account) {
+                accounts.push(account.clone());
+            }
+        };
+        for op in &self.ops {
+            match op {
+                Op::Deposit { account, .. } | Op::Withdraw { account, .. } => add(account),
+                Op::Transfer { from, to, .. } => {
+                    add(from);
+                    add(to);
+                }
+            }
+        }
+        for (account, _) in &self.initial {
+            add(account);
+        }
+        accounts
+    }
 }
 
 impl fmt::Display for FuzzFailure {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headline = match self.harness_fault {
+            Some(_) => "harness fault: the Lean model and the Rust reference disagree",
+            None => "mismatch detected",
+        };
         writeln!(
             f,
-            "\nmismatch detected (case={}, step={}, seed={})",
-            self.case_index, self.step_index, self.seed
+            "\n{} (case={}, step={}, seed={})",
+            headline, self.case_index, self.step_index, self.seed
         )?;
         writeln!(f, "----------------------------------------")?;
         writeln!(f, "op            : {}", self.op)?;
@@ -50,15 +337,28 @@ impl fmt::Display for FuzzFailure {
             Ok(()) => writeln!(f, "model         : {:?}", self.model_pre_snapshot)?,
             Err(e) => writeln!(f, "model         : Err({:?})", e)?,
         }
-        match &self.impl_result {
-            Ok(()) => writeln!(f, "impl          : {:?}", self.impl_pre_snapshot)?,
-            Err(e) => writeln!(f, "impl          : Err({:?})", e)?,
+        match &self.impl_outcome {
+            ImplOutcome::Returned(Ok(())) => {
+                writeln!(f, "impl          : {:?}", self.impl_pre_snapshot)?
+            }
+            ImplOutcome::Returned(Err(e)) => writeln!(f, "impl          : Err({:?})", e)?,
+            ImplOutcome::Panicked(message) => writeln!(f, "impl          : panicked: {}", message)?,
+        }
+        if let Some(fault) = &self.harness_fault {
+            match (&self.model_result, self.history.last()) {
+                (Ok(()), Some(step)) => writeln!(f, "model after   : {:?}", step.model_snapshot)?,
+                (Ok(()), None) => writeln!(f, "model after   : []")?,
+                (Err(e), _) => writeln!(f, "model after   : Err({:?})", e)?,
+            }
+            match &fault.reference_result {
+                Ok(()) => writeln!(f, "reference     : {:?}", fault.reference_snapshot)?,
+                Err(e) => writeln!(f, "reference     : Err({:?})", e)?,
+            }
         }
         writeln!(
             f,
-            "replay        : cargo run -- replay --seed {} --steps {}",
-            self.seed,
-            self.history.len()
+            "replay        : cargo run -- replay {}",
+            self.replay_args
         )?;
         writeln!(f, "history:")?;
         if let Some(_first) = self.history.first() {
@@ -73,11 +373,25 @@ impl fmt::Display for FuzzFailure {
                 Ok(()) => writeln!(f, "     model: {:?}", step.model_snapshot)?,
                 Err(e) => writeln!(f, "     model: Err({:?})", e)?,
             }
-            match &step.impl_result {
-                Ok(()) => writeln!(f, "     impl : {:?}", step.impl_snapshot)?,
-                Err(e) => writeln!(f, "     impl : Err({:?})", e)?,
+            match &step.impl_outcome {
+                ImplOutcome::Returned(Ok(())) => {
+                    writeln!(f, "     impl : {:?}", step.impl_snapshot)?
+                }
+                ImplOutcome::Returned(Err(e)) => writeln!(f, "     impl : Err({:?})", e)?,
+                ImplOutcome::Panicked(message) => writeln!(f, "     impl : panicked: {}", message)?,
             }
         }
+        if let Some(trace) = &self.minimized {
+            writeln!(
+                f,
+                "minimized     : {} of {} ops",
+                trace.ops.len(),
+                self.history.len()
+            )?;
+            writeln!(f, "  initial: {:?}", trace.initial)?;
+            let ops: Vec<String> = trace.ops.iter().map(|op| op.to_string()).collect();
+            writeln!(f, "  ops    : {}", ops.join("; "))?;
+        }
         Ok(())
     }
 }
@@ -89,102 +403,899 @@ pub fn seed_from_time() -> u64 {
     now.as_secs() ^ ((now.subsec_nanos() as u64) << 32)
 }
 
-pub fn run(config: &FuzzConfig) -> Result<(), FuzzFailure> {
-    for case_index in 0..config.cases {
-        let case_seed = config.seed.wrapping_add(case_index);
-        if let Err(failure) = run_case(config, case_index, case_seed) {
-            return Err(failure);
+/// Totals over one or more cases.
+#[derive(Clone, Debug, Default)]
+pub struct RunStats {
+    pub cases: u64,
+    /// Cases that ended in a mismatch.
+    pub failed: u64,
+    /// Of those, the ones where the Lean model and the Rust reference
+    /// disagreed.
+    pub harness_faults: u64,
+    /// Ops applied to both sides.
+    pub steps: u64,
+    /// Ops dropped because the Lean FFI cannot carry one of their account
+    /// names; they are applied to neither side.
+    pub skipped: u64,
+    /// Steps per `Proofs.lean` theorem, over every case including failed ones.
+    pub coverage: Coverage,
+}
+
+impl RunStats {
+    fn add_case(&mut self, outcome: &Result<RunStats, FuzzFailure>) {
+        match outcome {
+            Ok(stats) => {
+                self.cases += stats.cases;
+                self.steps += stats.steps;
+                self.skipped += stats.skipped;
+                self.coverage.add(&stats.coverage);
+            }
+            Err(failure) => {
+                self.cases += 1;
+                self.failed += 1;
+                if failure.harness_fault.is_some() {
+                    self.harness_faults += 1;
+                }
+                self.steps += failure.history.len() as u64;
+                self.coverage
+                    .record_history(&failure.initial, &failure.history);
+            }
+        }
+    }
+}
+
+/// One case's outcome and how long it took.
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    pub case_index: u64,
+    pub seed: u64,
+    pub elapsed: Duration,
+    pub outcome: Result<RunStats, FuzzFailure>,
+}
+
+/// The cases a run kept, in case order, and totals over every case it ran.
+///
+/// Bounded runs keep every case. Campaigns (`duration` or `soak`) can run for
+/// hours, so they only keep their first failure, or with `keep_going` one
+/// representative per signature.
+#[derive(Clone, Debug, Default)]
+pub struct RunReport {
+    pub cases: Vec<CaseResult>,
+    pub totals: RunStats,
+    /// Failures grouped by signature, for `keep_going` runs.
+    pub buckets: Option<Vec<Bucket>>,
+    pub elapsed: Duration,
+    /// The run was stopped from outside before its budget ran out.
+    pub interrupted: bool,
+    /// `LedgerUnderTest::name` of the implementation checked.
+    pub implementation: String,
+}
+
+impl RunReport {
+    pub fn failures(&self) -> impl Iterator<Item = &FuzzFailure> {
+        self.cases
+            .iter()
+            .filter_map(|case| case.outcome.as_ref().err())
+    }
+}
+
+/// Hooks for driving a run from outside.
+pub struct RunControl<'a> {
+    /// Once set, workers stop claiming new cases (e.g. from a SIGINT
+    /// handler); the cases already running still finish.
+    pub stop: &'a AtomicBool,
+    /// How often `on_progress` is called, if at all.
+    pub progress_every: Option<Duration>,
+    pub on_progress: &'a (dyn Fn(&Progress) + Sync),
+}
+
+/// A snapshot of a run that is still going.
+pub struct Progress<'a> {
+    pub totals: &'a RunStats,
+    /// Distinct failure signatures so far, for `keep_going` campaigns.
+    pub signatures: Option<usize>,
+    pub elapsed: Duration,
+}
+
+/// Runs `config.cases` cases (or until `config.duration` runs out, or until
+/// stopped), stopping at the first failure unless `keep_going` is set. That
+/// failure is shrunk; with `keep_going` failures are grouped by signature
+/// and one representative of each is shrunk.
+///
+/// With `config.jobs > 1` cases run on that many worker threads. Each case is
+/// seeded from its index alone and results come back in case order, so a
+/// bounded run reports the same whatever the thread count: without
+/// `keep_going`, workers stop claiming cases past the lowest failing index,
+/// and everything after it is dropped.
+pub fn run(config: &FuzzConfig, keep_going: bool, control: &RunControl) -> RunReport {
+    let started = Instant::now();
+    let deadline = config.duration.map(|duration| started + duration);
+    let campaign = config.is_campaign();
+    let jobs = config
+        .jobs
+        .clamp(1, config.cases.max(1).min(usize::MAX as u64) as usize);
+
+    let next_case = AtomicU64::new(0);
+    let first_failure = AtomicU64::new(u64::MAX);
+    let collector = Mutex::new(Collector {
+        cases: Vec::new(),
+        totals: RunStats::default(),
+        buckets: Vec::new(),
+        last_progress: started,
+    });
+    // also used for shrinking once the workers are done
+    let mut sut = config.implementation.build();
+    let worker = |sut: &mut dyn LedgerUnderTest| loop {
+        if control.stop.load(Ordering::Relaxed)
+            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            break;
+        }
+        let case_index = next_case.fetch_add(1, Ordering::Relaxed);
+        if case_index >= config.cases || case_index > first_failure.load(Ordering::Relaxed) {
+            break;
+        }
+        let result = run_one(config, case_index, sut);
+        if result.outcome.is_err() && !keep_going {
+            first_failure.fetch_min(case_index, Ordering::Relaxed);
+        }
+
+        let mut collector = collector.lock().expect("collector lock");
+        collector.add(result, campaign, keep_going);
+        if let Some(every) = control.progress_every {
+            if collector.last_progress.elapsed() >= every {
+                collector.last_progress = Instant::now();
+                (control.on_progress)(&Progress {
+                    totals: &collector.totals,
+                    signatures: keep_going.then_some(collector.buckets.len()),
+                    elapsed: started.elapsed(),
+                });
+            }
+        }
+    };
+
+    if jobs == 1 {
+        worker(&mut *sut);
+    } else {
+        // the main thread has to bring the Lean runtime up before any worker
+        lean_model::initialize();
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| {
+                    let _lean = LeanThread::attach();
+                    worker(&mut *config.implementation.build());
+                });
+            }
+        });
+    }
+
+    let collector = collector.into_inner().expect("collector lock");
+    let mut cases = collector.cases;
+    cases.sort_by_key(|case| case.case_index);
+    let mut totals = collector.totals;
+    let mut buckets = None;
+
+    if keep_going {
+        let mut grouped = if campaign {
+            collector.buckets
+        } else {
+            triage::dedup(cases.iter().filter_map(|case| case.outcome.as_ref().err()))
+        };
+        if config.shrink {
+            triage::shrink_representatives(&mut grouped, &mut *sut);
+        }
+        buckets = Some(grouped);
+    } else if let Some(first) = cases.iter().position(|case| case.outcome.is_err()) {
+        cases.truncate(first + 1);
+        if config.shrink {
+            if let Err(failure) = &mut cases[first].outcome {
+                failure.minimized = Some(shrink::shrink(failure, &mut *sut));
+            }
+        }
+    }
+    // bounded runs keep every case, so their totals can leave out whatever
+    // other workers ran past the first failure
+    if !campaign {
+        totals = RunStats::default();
+        for case in &cases {
+            totals.add_case(&case.outcome);
+        }
+    }
+
+    RunReport {
+        cases,
+        totals,
+        buckets,
+        elapsed: started.elapsed(),
+        interrupted: control.stop.load(Ordering::Relaxed),
+        implementation: sut.name().to_string(),
+    }
+}
+
+struct Collector {
+    cases: Vec<CaseResult>,
+    totals: RunStats,
+    // only filled for keep-going campaigns
+    buckets: Vec<Bucket>,
+    last_progress: Instant,
+}
+
+impl Collector {
+    fn add(&mut self, result: CaseResult, campaign: bool, keep_going: bool) {
+        self.totals.add_case(&result.outcome);
+        if !campaign {
+            self.cases.push(result);
+            return;
+        }
+        if let Err(failure) = &result.outcome {
+            if keep_going {
+                triage::add(&mut self.buckets, failure);
+            } else {
+                self.cases.push(result);
+            }
         }
     }
-    Ok(())
 }
 
-fn run_case(config: &FuzzConfig, case_index: u64, seed: u64) -> Result<(), FuzzFailure> {
+fn run_one(config: &FuzzConfig, case_index: u64, sut: &mut dyn LedgerUnderTest) -> CaseResult {
+    let seed = config.seed.wrapping_add(case_index);
+    let started = Instant::now();
+    let outcome = run_case(config, case_index, seed, sut).map_err(|failure| *failure);
+    CaseResult {
+        case_index,
+        seed,
+        elapsed: started.elapsed(),
+        outcome,
+    }
+}
+
+fn run_case(
+    config: &FuzzConfig,
+    case_index: u64,
+    seed: u64,
+    sut: &mut dyn LedgerUnderTest,
+) -> Result<RunStats, Box<FuzzFailure>> {
     let (mut init_rng, mut rng) = make_rng_streams(seed);
-    let accounts = default_accounts();
+    let mut account_rng = XorShift64::new(mix_seed(seed, 0x7f4a_7c15_9e37_79b9));
+    let accounts = accounts::case_accounts(&config.accounts, &mut account_rng);
+    let initial = initial_balances(&accounts, config.max_amount, &mut init_rng);
+
+    let mut slice = if config.swarm {
+        swarm_slice(seed, &config.op_weights, &accounts)
+    } else {
+        Slice {
+            op_weights: config.op_weights.clone(),
+            accounts: accounts.clone(),
+        }
+    };
+
+    // --steps counts generated ops, including any the model skipped, so it is
+    // the loop count rather than the history length
+    let with_replay_args = |steps: usize| {
+        move |mut failure: Box<FuzzFailure>| {
+            failure.replay_args = config.replay_args(seed, steps);
+            failure
+        }
+    };
+    let mut session =
+        Session::new(seed, case_index, initial, config.steps, sut).map_err(with_replay_args(0))?;
+    // partitions this case has hit so far, for --directed
+    let mut covered = Coverage::default();
+    for step in 0..config.steps {
+        let balances = session.model.snapshot();
+        let directed = if config.directed && rng.next_u64().is_multiple_of(2) {
+            directed_op(&mut rng, &slice, &balances, &covered, config)
+        } else {
+            None
+        };
+        let op = directed.unwrap_or_else(|| random_op(&mut rng, &mut slice, &balances, config));
+        if config.directed {
+            covered.record(&balances, &op);
+        }
+        session.step(op).map_err(with_replay_args(step + 1))?;
+    }
+
+    Ok(session.stats())
+}
+
+/// Runs a trace loaded from `path` as a single case, shrinking it on failure
+/// like a generated one. Nothing is generated, so the case reports seed 0.
+pub fn run_trace(config: &FuzzConfig, trace: &Trace, path: &Path) -> RunReport {
+    let started = Instant::now();
+    let mut sut = config.implementation.build();
+    let mut outcome = Session::new(0, 0, trace.initial.clone(), trace.ops.len(), &mut *sut)
+        .and_then(|mut session| {
+            for op in &trace.ops {
+                session.step(op.clone())?;
+            }
+            Ok(session.stats())
+        })
+        .map_err(|failure| *failure);
+    if let Err(failure) = &mut outcome {
+        failure.replay_args = format!("--file {}", path.display());
+        if config.shrink {
+            failure.minimized = Some(shrink::shrink(failure, &mut *sut));
+        }
+    }
+    let elapsed = started.elapsed();
+    let mut totals = RunStats::default();
+    totals.add_case(&outcome);
+    RunReport {
+        cases: vec![CaseResult {
+            case_index: 0,
+            seed: 0,
+            elapsed,
+            outcome,
+        }],
+        totals,
+        buckets: None,
+        elapsed,
+        interrupted: false,
+        implementation: sut.name().to_string(),
+    }
+}
 
-    let mut model = LeanLedger::new();
-    let mut ledger = Ledger::new();
-    let mut history = Vec::with_capacity(config.steps);
+/// Replays `trace` against a fresh model and `sut` after a reset, failing on
+/// the first step where they disagree.
+pub fn replay_trace(
+    trace: &Trace,
+    seed: u64,
+    case_index: u64,
+    sut: &mut dyn LedgerUnderTest,
+) -> Result<(), Box<FuzzFailure>> {
+    let mut session = Session::new(
+        seed,
+        case_index,
+        trace.initial.clone(),
+        trace.ops.len(),
+        sut,
+    )?;
+    for op in &trace.ops {
+        session.step(op.clone())?;
+    }
+    Ok(())
+}
 
-    seed_initial_balances(
-        &mut model,
-        &mut ledger,
-        &accounts,
-        config.max_amount,
-        &mut init_rng,
-    );
+struct Session<'a> {
+    seed: u64,
+    case_index: u64,
+    initial: Vec<(String, u64)>,
+    model: LeanLedger,
+    // checks the FFI glue; None when the model is this same reference
+    reference: Option<ReferenceModel>,
+    ledger: &'a mut dyn LedgerUnderTest,
+    history: Vec<StepRecord>,
+    skipped: u64,
+    // a panic from resetting the implementation, reported on the first step
+    reset_panic: Option<String>,
+}
 
-    for step_index in 0..config.steps {
-        let model_pre_snapshot = model.snapshot(&accounts);
-        let impl_pre_snapshot = ledger.snapshot();
+impl<'a> Session<'a> {
+    // seeds `initial` on both sides. The seeding deposits are checked like
+    // steps: one the implementation gets wrong fails the case right there,
+    // with the balances seeded before it as the initial state
+    fn new(
+        seed: u64,
+        case_index: u64,
+        mut initial: Vec<(String, u64)>,
+        steps: usize,
+        ledger: &'a mut dyn LedgerUnderTest,
+    ) -> Result<Self, Box<FuzzFailure>> {
+        // balances the model could never hold are not seeded on either side
+        initial.retain(|(account, _)| lean_model::check_account(account).is_ok());
+        let mut reset_panic = None;
+        call_impl(ledger, &mut reset_panic, Some("reset"), |ledger| {
+            ledger.reset()
+        });
+        let mut session = Self {
+            seed,
+            case_index,
+            initial: Vec::with_capacity(initial.len()),
+            model: LeanLedger::new(),
+            reference: lean_model::VERIFIED_MODEL.then(ReferenceModel::new),
+            ledger,
+            history: Vec::with_capacity(steps),
+            skipped: 0,
+            reset_panic,
+        };
+        for (account, amount) in initial {
+            session.step(Op::Deposit {
+                account: account.clone(),
+                amount,
+            })?;
+            session.history.clear();
+            session.initial.push((account, amount));
+        }
+        Ok(session)
+    }
 
-        let op = random_op(&mut rng, &accounts, config.max_amount);
+    fn step(&mut self, op: Op) -> Result<(), Box<FuzzFailure>> {
+        let step_index = self.history.len();
+        let model_pre_snapshot = self.model.snapshot();
 
-        let model_result = model.apply(&op);
-        let impl_result = ledger.apply(&op);
+        let model_result = match self.model.apply(&op) {
+            Ok(result) => result,
+            Err(_) => {
+                self.skipped += 1;
+                return Ok(());
+            }
+        };
+        let reference_result = self
+            .reference
+            .as_mut()
+            .map(|reference| reference.apply(&op));
 
-        let model_snapshot = model.snapshot(&accounts);
-        let impl_snapshot = ledger.snapshot();
+        // a panic while resetting or snapshotting the implementation fails
+        // the step like one while applying, and skips its remaining calls
+        let mut impl_panic = self.reset_panic.take();
+        let impl_pre_snapshot = call_impl(
+            self.ledger,
+            &mut impl_panic,
+            Some("snapshot before the op"),
+            |ledger| ledger.snapshot(),
+        )
+        .unwrap_or_default();
+        let impl_result = call_impl(self.ledger, &mut impl_panic, None, |ledger| {
+            ledger.apply(&op)
+        });
+        let model_snapshot = self.model.snapshot();
+        let impl_snapshot = call_impl(
+            self.ledger,
+            &mut impl_panic,
+            Some("snapshot after the op"),
+            |ledger| ledger.snapshot(),
+        )
+        .unwrap_or_default();
+        let impl_outcome = match impl_panic {
+            Some(message) => ImplOutcome::Panicked(message),
+            None => ImplOutcome::Returned(impl_result.expect("apply returned")),
+        };
 
-        history.push(StepRecord {
+        self.history.push(StepRecord {
             op: op.clone(),
             model_result: model_result.clone(),
-            impl_result: impl_result.clone(),
+            impl_outcome: impl_outcome.clone(),
             model_snapshot: model_snapshot.clone(),
             impl_snapshot: impl_snapshot.clone(),
         });
 
-        let results_match = model_result == impl_result;
+        let harness_fault = self.reference.as_ref().and_then(|reference| {
+            let reference_result = reference_result.expect("applied to the reference");
+            let reference_snapshot = reference.snapshot();
+            (reference_result != model_result || reference_snapshot != model_snapshot).then_some(
+                HarnessFault {
+                    reference_result,
+                    reference_snapshot,
+                },
+            )
+        });
+        let results_match = impl_outcome.matches(&model_result);
         let state_match = model_snapshot == impl_snapshot;
 
-        if !results_match || !state_match {
-            return Err(FuzzFailure {
-                seed,
-                case_index,
+        if harness_fault.is_some() || !results_match || !state_match {
+            return Err(Box::new(FuzzFailure {
+                seed: self.seed,
+                case_index: self.case_index,
                 step_index,
                 op,
                 model_result,
-                impl_result,
+                impl_outcome,
                 model_pre_snapshot,
                 impl_pre_snapshot,
-                history,
+                initial: self.initial.clone(),
+                history: std::mem::take(&mut self.history),
+                minimized: None,
+                replay_args: String::new(),
+                harness_fault,
+            }));
+        }
+
+        Ok(())
+    }
+
+    // a passing case's contribution to the run totals
+    fn stats(&self) -> RunStats {
+        let mut coverage = Coverage::default();
+        coverage.record_history(&self.initial, &self.history);
+        RunStats {
+            cases: 1,
+            failed: 0,
+            harness_faults: 0,
+            steps: self.history.len() as u64,
+            skipped: self.skipped,
+            coverage,
+        }
+    }
+}
+
+/// The op kinds and accounts a case draws from. Outside swarm mode this is
+/// everything in the config; freshly invented accounts join it as they are
+/// used.
+#[derive(Clone, Debug)]
+struct Slice {
+    op_weights: OpWeights,
+    accounts: Vec<String>,
+}
+
+// swarm testing: each case switches every op kind and account on or off at
+// random, keeping at least one op kind and (when transfers are on) two accounts
+fn swarm_slice(seed: u64, op_weights: &OpWeights, accounts: &[String]) -> Slice {
+    let mut rng = XorShift64::new(mix_seed(seed, 0x3c6e_f372_fe94_f82b));
+
+    let mut weights = op_weights.weights();
+    let enabled: Vec<usize> = (0..weights.len()).filter(|i| weights[*i] > 0).collect();
+    // with every weight at 0 there is nothing to switch off, and random_op
+    // falls back to deposits as it does outside swarm mode
+    if !enabled.is_empty() {
+        let keep = enabled[(rng.next_u64() % enabled.len() as u64) as usize];
+        for index in enabled {
+            if index != keep && rng.next_u64().is_multiple_of(2) {
+                weights[index] = 0;
+            }
+        }
+    }
+    let op_weights = OpWeights::from_weights(weights);
+
+    let mut kept: Vec<String> = accounts
+        .iter()
+        .filter(|_| rng.next_u64().is_multiple_of(2))
+        .cloned()
+        .collect();
+    let min_accounts = if op_weights.transfer > 0 { 2 } else { 1 };
+    while kept.len() < min_accounts.min(accounts.len()) {
+        let candidate = pick_account(&mut rng, accounts);
+        if !kept.contains(candidate) {
+            kept.push(candidate.clone());
+        }
+    }
+
+    Slice {
+        op_weights,
+        accounts: kept,
+    }
+}
+
+fn random_op(
+    rng: &mut XorShift64,
+    slice: &mut Slice,
+    balances: &[(String, u64)],
+    config: &FuzzConfig,
+) -> Op {
+    match pick_weighted(rng, &slice.op_weights.weights()) {
+        Some(0) | None => {
+            let account = pick_or_invent_account(rng, slice, config);
+            let balance = balance_of(balances, &account);
+            let amount = random_amount(rng, balance, balance, config);
+            Op::Deposit { account, amount }
+        }
+        Some(1) => {
+            let account = pick_or_invent_account(rng, slice, config);
+            let balance = balance_of(balances, &account);
+            let amount = random_amount(rng, balance, balance, config);
+            Op::Withdraw { account, amount }
+        }
+        Some(_) => {
+            let from = pick_or_invent_account(rng, slice, config);
+            let mut to = pick_or_invent_account(rng, slice, config);
+            if from == to {
+                to = pick_different_account(rng, &slice.accounts, &from).clone();
+            }
+            let amount = random_amount(
+                rng,
+                balance_of(balances, &from),
+                balance_of(balances, &to),
+                config,
+            );
+            Op::Transfer { from, to, amount }
+        }
+    }
+}
+
+// spec-directed generation: an op aimed at the partition this case has hit
+// least (a random one among ties), or when the state cannot reach it yet, a
+// deposit that builds towards it. None when the slice allows neither
+fn directed_op(
+    rng: &mut XorShift64,
+    slice: &Slice,
+    balances: &[(String, u64)],
+    covered: &Coverage,
+    config: &FuzzConfig,
+) -> Option<Op> {
+    let weights = slice.op_weights.weights();
+    let enabled = |op: &str| {
+        OpWeights::KEYS
+            .iter()
+            .position(|key| *key == op)
+            .is_some_and(|index| weights[index] > 0)
+    };
+    let accounts = &slice.accounts;
+    let reachable: Vec<Class> = Class::ALL
+        .into_iter()
+        .filter(|class| enabled(class.op()))
+        .filter(|class| match class {
+            Class::TransferOther => accounts.len() >= 3,
+            Class::DepositOther
+            | Class::WithdrawOther
+            | Class::TransferFail
+            | Class::TransferOverflow
+            | Class::TransferFromTo
+            | Class::TransferFromToExact => accounts.len() >= 2,
+            _ => true,
+        })
+        .collect();
+    let fewest = reachable.iter().map(|class| covered.count(*class)).min()?;
+    let targets: Vec<Class> = reachable
+        .into_iter()
+        .filter(|class| covered.count(*class) == fewest)
+        .collect();
+    let target = targets[(rng.next_u64() % targets.len() as u64) as usize];
+
+    let balance = |account: &str| balance_of(balances, account);
+    let held = |account: &String| balance(account) > 0;
+    let uniform = 1 + rng.next_u64() % config.max_amount.max(1);
+    let mut pick = |accept: &dyn Fn(&String) -> bool| {
+        let candidates: Vec<&String> = accounts.iter().filter(|account| accept(account)).collect();
+        (!candidates.is_empty())
+            .then(|| candidates[(rng.next_u64() % candidates.len() as u64) as usize].clone())
+    };
+    let other_held = |excluded: &[&String]| {
+        accounts
+            .iter()
+            .any(|account| held(account) && !excluded.contains(&account))
+    };
+
+    let op = match target {
+        Class::DepositSelf => pick(&|account| balance(account) < u64::MAX).map(|account| {
+            let amount = uniform.min(u64::MAX - 1 - balance(&account));
+            Op::Deposit { account, amount }
+        }),
+        Class::DepositSelfAtMax => pick(&|_| true).map(|account| {
+            let amount = u64::MAX - balance(&account);
+            Op::Deposit { account, amount }
+        }),
+        Class::DepositOther => {
+            pick(&|account| balance(account) < u64::MAX && other_held(&[account])).map(|account| {
+                let amount = uniform.min(u64::MAX - 1 - balance(&account));
+                Op::Deposit { account, amount }
+            })
+        }
+        Class::DepositOverflow => pick(&held).map(|account| {
+            let amount = u64::MAX - balance(&account) + 1;
+            Op::Deposit { account, amount }
+        }),
+        Class::WithdrawFail => pick(&|account| balance(account) < u64::MAX).map(|account| {
+            let amount = balance(&account) + 1;
+            Op::Withdraw { account, amount }
+        }),
+        Class::WithdrawSelf => pick(&held).map(|account| {
+            let amount = uniform % balance(&account);
+            Op::Withdraw { account, amount }
+        }),
+        Class::WithdrawSelfExact => pick(&held).map(|account| {
+            let amount = balance(&account);
+            Op::Withdraw { account, amount }
+        }),
+        Class::WithdrawOther => pick(&|account| other_held(&[account])).map(|account| {
+            let amount = uniform.min(balance(&account));
+            Op::Withdraw { account, amount }
+        }),
+        Class::TransferFail => pick(&|account| balance(account) < u64::MAX).and_then(|from| {
+            let to = pick(&|account| *account != from)?;
+            let amount = balance(&from) + 1;
+            Some(Op::Transfer { from, to, amount })
+        }),
+        Class::TransferFailSameAccount => {
+            pick(&|account| balance(account) < u64::MAX).map(|from| {
+                let amount = balance(&from) + 1;
+                Op::Transfer {
+                    to: from.clone(),
+                    from,
+                    amount,
+                }
+            })
+        }
+        Class::TransferOverflow => {
+            let from = pick(&|from| {
+                accounts.iter().any(|to| {
+                    to != from && held(to) && balance(from).checked_add(balance(to)).is_none()
+                })
             });
+            match from {
+                Some(from) => pick(&|to| {
+                    *to != from && held(to) && balance(&from).checked_add(balance(to)).is_none()
+                })
+                .map(|to| {
+                    let amount = u64::MAX - balance(&to) + 1;
+                    Op::Transfer { from, to, amount }
+                }),
+                // fill one account to the brim; the general setup below then
+                // gives another one a balance to send it
+                None => match pick(&|account| balance(account) == u64::MAX) {
+                    Some(_) => None,
+                    None => pick(&|_| true).map(|account| {
+                        let amount = u64::MAX - balance(&account);
+                        Op::Deposit { account, amount }
+                    }),
+                },
+            }
         }
+        Class::TransferFromTo | Class::TransferFromToExact | Class::TransferOther => {
+            let exact = target == Class::TransferFromToExact;
+            let third = target == Class::TransferOther;
+            pick(&held).and_then(|from| {
+                let amount = if exact {
+                    balance(&from)
+                } else {
+                    uniform % balance(&from)
+                };
+                let to = pick(&|to| {
+                    *to != from
+                        && balance(to).checked_add(amount).is_some()
+                        && (!third || other_held(&[&from, to]))
+                })?;
+                Some(Op::Transfer { from, to, amount })
+            })
+        }
+        Class::TransferSameAccount => pick(&|_| true).map(|from| {
+            let amount = uniform.min(balance(&from));
+            Op::Transfer {
+                to: from.clone(),
+                from,
+                amount,
+            }
+        }),
+    };
+
+    // setup: give an account with nothing in it (or any that has room) a
+    // balance, which is what most unreachable partitions are waiting for
+    let setup = || {
+        if !enabled("deposit") {
+            return None;
+        }
+        let account = pick(&|account| !held(account))
+            .or_else(|| pick(&|account| balance(account).checked_add(uniform).is_some()))?;
+        Some(Op::Deposit {
+            account,
+            amount: uniform,
+        })
+    };
+    op.or_else(setup)
+}
+
+fn pick_or_invent_account(rng: &mut XorShift64, slice: &mut Slice, config: &FuzzConfig) -> String {
+    if config.fresh_accounts > 0 && rng.next_u64() % 100 < config.fresh_accounts as u64 {
+        let account = accounts::fresh_account(&config.accounts, &slice.accounts, rng);
+        slice.accounts.push(account.clone());
+        return account;
     }
+    pick_account(rng, &slice.accounts).clone()
+}
 
-    Ok(())
+// `balance` is the account the op debits (or deposits to), `credited` the one
+// that would overflow; they only differ for transfers
+fn random_amount(rng: &mut XorShift64, balance: u64, credited: u64, config: &FuzzConfig) -> u64 {
+    let uniform = |rng: &mut XorShift64| 1 + rng.next_u64() % config.max_amount.max(1);
+    match pick_weighted(rng, &config.amount_mix.weights()) {
+        Some(0) | None => uniform(rng),
+        Some(1) => balance,
+        Some(2) => {
+            if rng.next_u64().is_multiple_of(2) {
+                balance.saturating_sub(1)
+            } else {
+                balance.saturating_add(1)
+            }
+        }
+        Some(3) => 0,
+        Some(4) => u64::MAX,
+        Some(_) => {
+            let headroom = u64::MAX - credited;
+            if rng.next_u64().is_multiple_of(2) {
+                headroom
+            } else {
+                headroom.saturating_add(1)
+            }
+        }
+    }
 }
 
-fn random_op(rng: &mut XorShift64, accounts: &[String], max_amount: u64) -> Op {
-    let roll = rng.next_u64() % 100;
-    let amount = 1 + rng.next_u64() % max_amount.max(1);
+fn balance_of(balances: &[(String, u64)], account: &str) -> u64 {
+    balances
+        .iter()
+        .find(|(name, _)| name == account)
+        .map_or(0, |(_, balance)| *balance)
+}
 
-    if roll < 45 {
-        let account = pick_account(rng, accounts).clone();
-        Op::Deposit { account, amount }
-    } else if roll < 75 {
-        let account = pick_account(rng, accounts).clone();
-        Op::Withdraw { account, amount }
-    } else {
-        let from = pick_account(rng, accounts).clone();
-        let mut to = pick_account(rng, accounts).clone();
-        if from == to {
-            to = pick_different_account(rng, accounts, &from).clone();
+// index drawn in proportion to `weights`, or None if they are all zero
+fn pick_weighted(rng: &mut XorShift64, weights: &[u32]) -> Option<usize> {
+    let total: u64 = weights.iter().map(|weight| *weight as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.next_u64() % total;
+    for (index, weight) in weights.iter().enumerate() {
+        let weight = *weight as u64;
+        if roll < weight {
+            return Some(index);
         }
-        Op::Transfer { from, to, amount }
+        roll -= weight;
     }
+    None
 }
 
 #[derive(Clone, Debug)]
 pub struct StepRecord {
     pub op: Op,
     pub model_result: Result<(), ApplyError>,
-    pub impl_result: Result<(), ApplyError>,
+    pub impl_outcome: ImplOutcome,
     pub model_snapshot: Vec<(String, u64)>,
     pub impl_snapshot: Vec<(String, u64)>,
 }
 
+thread_local! {
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    static LAST_PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+// runs `f`, turning a panic into its message instead of unwinding further.
+// the default hook stays in place for every other panic, it is only muted
+// while an implementation step runs on this thread
+fn catch_impl_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CAPTURING.with(|capturing| capturing.get()) {
+                let location = info.location().map(|location| location.to_string());
+                LAST_PANIC_LOCATION.with(|last| *last.borrow_mut() = location);
+            } else {
+                previous(info);
+            }
+        }));
+    });
+
+    CAPTURING.with(|capturing| capturing.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CAPTURING.with(|capturing| capturing.set(false));
+
+    result.map_err(|payload| {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "<non-string panic payload>".to_string()
+        };
+        match LAST_PANIC_LOCATION.with(|last| last.borrow_mut().take()) {
+            Some(location) => format!("{} (at {})", message, location),
+            None => message,
+        }
+    })
+}
+
+// calls into the implementation unless an earlier call for the same step
+// panicked. A panic is kept in `panicked`, prefixed with `during` if given
+fn call_impl<T>(
+    ledger: &mut dyn LedgerUnderTest,
+    panicked: &mut Option<String>,
+    during: Option<&str>,
+    f: impl FnOnce(&mut dyn LedgerUnderTest) -> T,
+) -> Option<T> {
+    if panicked.is_some() {
+        return None;
+    }
+    match catch_impl_panic(|| f(ledger)) {
+        Ok(value) => Some(value),
+        Err(message) => {
+            *panicked = Some(match during {
+                Some(during) => format!("{}: {}", during, message),
+                None => message,
+            });
+            None
+        }
+    }
+}
+
 fn pick_account<'a>(rng: &mut XorShift64, accounts: &'a [String]) -> &'a String {
     let index = (rng.next_u64() % accounts.len() as u64) as usize;
     &accounts[index]
@@ -206,17 +1317,17 @@ fn pick_different_account<'a>(
 
 // xorshift prng
 #[derive(Clone, Debug)]
-struct XorShift64 {
+pub struct XorShift64 {
     state: u64,
 }
 
 impl XorShift64 {
-    fn new(seed: u64) -> Self {
+    pub fn new(seed: u64) -> Self {
         let seed = if seed == 0 { 0x9e3779b97f4a7c15 } else { seed };
         Self { state: seed }
     }
 
-    fn next_u64(&mut self) -> u64 {
+    pub fn next_u64(&mut self) -> u64 {
         let mut x = self.state;
         x ^= x << 13;
         x ^= x >> 7;
@@ -240,38 +1351,141 @@ fn mix_seed(seed: u64, stream: u64) -> u64 {
     z ^ (z >> 31)
 }
 
-fn seed_initial_balances(
-    model: &mut LeanLedger,
-    ledger: &mut Ledger,
+fn initial_balances(
     accounts: &[String],
     max_amount: u64,
     rng: &mut XorShift64,
-) {
+) -> Vec<(String, u64)> {
     let bound = max_amount.max(1);
+    let mut balances = Vec::with_capacity(accounts.len());
     for account in accounts {
         let amount = rng.next_u64() % bound.saturating_add(1);
         if amount == 0 {
             continue;
         }
-        let op = Op::Deposit {
-            account: account.clone(),
-            amount,
+        balances.push((account.clone(), amount));
+    }
+    balances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swarm_slice_with_every_op_weight_zero_generates_deposits() {
+        let config = FuzzConfig::new(7);
+        let accounts = vec!["alice".to_string(), "bob".to_string()];
+        for seed in 0..50 {
+            let mut slice = swarm_slice(seed, &OpWeights::from_weights([0, 0, 0]), &accounts);
+            assert_eq!(slice.op_weights.weights(), [0, 0, 0]);
+            let mut rng = XorShift64::new(seed);
+            let op = random_op(&mut rng, &mut slice, &[], &config);
+            assert!(matches!(op, Op::Deposit { .. }), "{}", op);
+        }
+    }
+
+    #[test]
+    fn replay_args_quote_account_names_for_the_shell() {
+        let mut config = FuzzConfig::new(1);
+        config.accounts = Accounts::Fixed(vec!["alice".to_string(), "bob".to_string()]);
+        assert_eq!(
+            config.replay_args(1, 4),
+            "--seed 1 --steps 4 --max-amount 50 --accounts alice,bob"
+        );
+
+        let names = ["", "b o b", "it's", "zoë", "$HOME", "tab\t"];
+        config.accounts = Accounts::Fixed(names.map(str::to_string).to_vec());
+        let args = config.replay_args(1, 4);
+        assert!(
+            args.ends_with(" --accounts ',b o b,it'\\''s,zoë,$HOME,tab\t'"),
+            "{}",
+            args
+        );
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("'"), r"''\'''");
+    }
+
+    #[test]
+    fn directed_cases_reach_every_class() {
+        // the op choice of run_case, with the reference model standing in for
+        // the Lean one so the test leaves the Lean runtime alone
+        let mut config = FuzzConfig::new(11);
+        config.directed = true;
+        config.steps = 100;
+        let mut total = Coverage::default();
+        for case in 0..3 {
+            let seed = config.seed + case;
+            let (_, mut rng) = make_rng_streams(seed);
+            let mut account_rng = XorShift64::new(mix_seed(seed, 0x7f4a_7c15_9e37_79b9));
+            let accounts = accounts::case_accounts(&config.accounts, &mut account_rng);
+            let mut slice = Slice {
+                op_weights: config.op_weights.clone(),
+                accounts,
+            };
+            let mut model = ReferenceModel::new();
+            let mut covered = Coverage::default();
+            for _ in 0..config.steps {
+                let balances = model.snapshot();
+                let directed = if rng.next_u64().is_multiple_of(2) {
+                    directed_op(&mut rng, &slice, &balances, &covered, &config)
+                } else {
+                    None
+                };
+                let op =
+                    directed.unwrap_or_else(|| random_op(&mut rng, &mut slice, &balances, &config));
+                covered.record(&balances, &op);
+                let _ = model.apply(&op);
+            }
+            total.add(&covered);
+        }
+        assert_eq!(total.missed().collect::<Vec<_>>(), [], "{}", total);
+    }
+
+    #[test]
+    fn pick_weighted_never_picks_a_zero_weight() {
+        let mut rng = XorShift64::new(3);
+        let mut picked = [0; 4];
+        for _ in 0..1000 {
+            let index = pick_weighted(&mut rng, &[0, 5, 0, 1]).expect("some weight is positive");
+            picked[index] += 1;
+        }
+        assert_eq!(picked[0], 0);
+        assert_eq!(picked[2], 0);
+        assert!(picked[1] > picked[3] && picked[3] > 0, "{:?}", picked);
+        assert_eq!(pick_weighted(&mut rng, &[0, 0]), None);
+        assert_eq!(pick_weighted(&mut rng, &[]), None);
+
+        let mut config = FuzzConfig::new(3);
+        config.op_weights = OpWeights::from_weights([1, 0, 3]);
+        config.fresh_accounts = 0;
+        let mut slice = Slice {
+            op_weights: config.op_weights.clone(),
+            accounts: vec!["alice".to_string(), "bob".to_string()],
         };
-        model
-            .apply(&op)
-            .expect("Lean model seeding deposit should not fail");
-        ledger
-            .apply(&op)
-            .expect("ledger seeding deposit should not fail");
-    }
-}
-
-fn default_accounts() -> Vec<String> {
-    vec![
-        "alice".to_string(),
-        "bob".to_string(),
-        "carol".to_string(),
-        "dave".to_string(),
-        "erin".to_string(),
-    ]
+        for _ in 0..1000 {
+            let op = random_op(&mut rng, &mut slice, &[], &config);
+            assert!(!matches!(op, Op::Withdraw { .. }), "{}", op);
+        }
+    }
+
+    #[test]
+    fn random_amount_reaches_the_boundaries() {
+        let config = FuzzConfig::new(5);
+        let mut rng = XorShift64::new(5);
+        let amounts: Vec<u64> = (0..2000)
+            .map(|_| random_amount(&mut rng, 7, 10, &config))
+            .collect();
+        for expected in [0, 1, 6, 7, 8, u64::MAX - 10, u64::MAX - 9, u64::MAX] {
+            assert!(amounts.contains(&expected), "{} never drawn", expected);
+        }
+        assert!(amounts
+            .iter()
+            .all(|amount| *amount <= config.max_amount || *amount >= u64::MAX - 10));
+
+        // with only the zero strategy on, nothing else comes out
+        let mut config = config;
+        config.amount_mix = AmountMix::from_weights([0, 0, 0, 1, 0, 0]);
+        assert!((0..100).all(|_| random_amount(&mut rng, 7, 10, &config) == 0));
+    }
 }
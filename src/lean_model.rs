@@ -1,133 +1,51 @@
 use std::ffi::CString;
-use std::os::raw::{c_char, c_void};
-use std::sync::Once;
-
-use crate::op::{ApplyError, Op};
-
-extern "C" {
-    fn ledger_lean_initialize();
-    fn ledger_lean_state_new() -> *mut c_void;
-    fn ledger_lean_state_dec(state: *mut c_void);
-    fn ledger_lean_apply_deposit(
-        state: *mut c_void,
-        account: *const c_char,
-        amount: u64,
-        ok: *mut u8,
-    ) -> *mut c_void;
-    fn ledger_lean_apply_withdraw(
-        state: *mut c_void,
-        account: *const c_char,
-        amount: u64,
-        ok: *mut u8,
-    ) -> *mut c_void;
-    fn ledger_lean_apply_transfer(
-        state: *mut c_void,
-        from_account: *const c_char,
-        to_account: *const c_char,
-        amount: u64,
-        ok: *mut u8,
-    ) -> *mut c_void;
-    fn ledger_lean_balance(state: *mut c_void, account: *const c_char) -> u64;
-}
-
-static INIT: Once = Once::new();
-
-fn ensure_initialized() {
-    INIT.call_once(|| unsafe {
-        ledger_lean_initialize();
-    });
+use std::fmt;
+
+#[cfg(not(feature = "lean-model"))]
+mod fallback;
+#[cfg(feature = "lean-model")]
+mod ffi;
+
+#[cfg(not(feature = "lean-model"))]
+pub use fallback::{initialize, LeanLedger, LeanThread};
+#[cfg(feature = "lean-model")]
+pub use ffi::{initialize, LeanLedger, LeanThread};
+
+/// What implementations are checked against, for reports. Without the
+/// `lean-model` feature nothing from Lean is built or linked and
+/// `LeanLedger` runs the Rust reference model instead.
+#[cfg(feature = "lean-model")]
+pub const ORACLE: &str = "the verified Lean model";
+#[cfg(not(feature = "lean-model"))]
+pub const ORACLE: &str =
+    "the Rust reference model (built without the lean-model feature, so not the verified Lean model)";
+
+pub const VERIFIED_MODEL: bool = cfg!(feature = "lean-model");
+
+/// An op the harness cannot hand to the Lean model at all, as opposed to one
+/// the model rejects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FfiError {
+    /// Account names cross the FFI as C strings, so they cannot contain NUL.
+    InteriorNul { account: String },
 }
 
-pub struct LeanLedger {
-    state: *mut c_void,
-}
-
-impl LeanLedger {
-    pub fn new() -> Self {
-        ensure_initialized();
-        let state = unsafe { ledger_lean_state_new() };
-        Self { state }
-    }
-
-    pub fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
-        let mut ok = 0u8;
-        let new_state = match op {
-            Op::Deposit { account, amount } => {
-                let account = CString::new(account.as_str()).expect("account contains NUL");
-                unsafe { ledger_lean_apply_deposit(self.state, account.as_ptr(), *amount, &mut ok) }
-            }
-            Op::Withdraw { account, amount } => {
-                let account = CString::new(account.as_str()).expect("account contains NUL");
-                unsafe {
-                    ledger_lean_apply_withdraw(self.state, account.as_ptr(), *amount, &mut ok)
-                }
-            }
-            Op::Transfer { from, to, amount } => {
-                let from_account = CString::new(from.as_str()).expect("from contains NUL");
-                let to_account = CString::new(to.as_str()).expect("to contains NUL");
-                unsafe {
-                    ledger_lean_apply_transfer(
-                        self.state,
-                        from_account.as_ptr(),
-                        to_account.as_ptr(),
-                        *amount,
-                        &mut ok,
-                    )
-                }
-            }
-        };
-
-        self.state = new_state;
-
-        if ok == 1 {
-            Ok(())
-        } else {
-            match op {
-                Op::Deposit { account, amount } => Err(ApplyError::InsufficientFunds {
-                    account: account.clone(),
-                    balance: self.balance(account),
-                    amount: *amount,
-                }),
-                Op::Withdraw { account, amount } => Err(ApplyError::InsufficientFunds {
-                    account: account.clone(),
-                    balance: self.balance(account),
-                    amount: *amount,
-                }),
-                Op::Transfer { from, amount, .. } => Err(ApplyError::InsufficientFunds {
-                    account: from.clone(),
-                    balance: self.balance(from),
-                    amount: *amount,
-                }),
+impl fmt::Display for FfiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiError::InteriorNul { account } => {
+                write!(f, "account name contains NUL: {:?}", account)
             }
         }
     }
+}
 
-    pub fn snapshot(&self, accounts: &[String]) -> Vec<(String, u64)> {
-        let mut items: Vec<(String, u64)> = accounts
-            .iter()
-            .filter_map(|account| {
-                let balance = self.balance(account);
-                if balance == 0 {
-                    None
-                } else {
-                    Some((account.clone(), balance))
-                }
-            })
-            .collect();
-        items.sort_by(|a, b| a.0.cmp(&b.0));
-        items
-    }
-
-    pub fn balance(&self, account: &str) -> u64 {
-        let account = CString::new(account).expect("account contains NUL");
-        unsafe { ledger_lean_balance(self.state, account.as_ptr()) }
-    }
+pub fn check_account(account: &str) -> Result<(), FfiError> {
+    account_cstring(account).map(|_| ())
 }
 
-impl Drop for LeanLedger {
-    fn drop(&mut self) {
-        unsafe {
-            ledger_lean_state_dec(self.state);
-        }
-    }
+fn account_cstring(account: &str) -> Result<CString, FfiError> {
+    CString::new(account).map_err(|_| FfiError::InteriorNul {
+        account: account.to_string(),
+    })
 }
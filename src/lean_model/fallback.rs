@@ -0,0 +1,43 @@
+use super::{check_account, FfiError};
+use crate::op::{ApplyError, Op};
+use crate::reference::ReferenceModel;
+
+/// Without the `lean-model` feature there is no Lean runtime to bring up.
+pub fn initialize() {}
+
+pub struct LeanThread;
+
+impl LeanThread {
+    pub fn attach() -> Self {
+        LeanThread
+    }
+}
+
+/// Stands in for the Lean model in builds without the `lean-model` feature,
+/// running `ReferenceModel` instead. Account names are still checked as if
+/// they crossed the FFI, so runs skip the same ops as with the real model.
+#[derive(Default)]
+pub struct LeanLedger {
+    model: ReferenceModel,
+}
+
+impl LeanLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, op: &Op) -> Result<Result<(), ApplyError>, FfiError> {
+        match op {
+            Op::Deposit { account, .. } | Op::Withdraw { account, .. } => check_account(account)?,
+            Op::Transfer { from, to, .. } => {
+                check_account(from)?;
+                check_account(to)?;
+            }
+        }
+        Ok(self.model.apply(op))
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        self.model.snapshot()
+    }
+}
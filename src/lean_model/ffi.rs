@@ -0,0 +1,220 @@
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
+use std::sync::Once;
+
+use super::{account_cstring, FfiError};
+use crate::op::{ApplyError, Op};
+
+extern "C" {
+    fn ledger_lean_initialize();
+    fn ledger_lean_initialize_thread();
+    fn ledger_lean_finalize_thread();
+    fn ledger_lean_state_new() -> *mut c_void;
+    fn ledger_lean_state_dec(state: *mut c_void);
+    fn ledger_lean_apply_deposit(
+        state: *mut c_void,
+        account: *const c_char,
+        amount: u64,
+        error: *mut RawError,
+    ) -> *mut c_void;
+    fn ledger_lean_apply_withdraw(
+        state: *mut c_void,
+        account: *const c_char,
+        amount: u64,
+        error: *mut RawError,
+    ) -> *mut c_void;
+    fn ledger_lean_apply_transfer(
+        state: *mut c_void,
+        from_account: *const c_char,
+        to_account: *const c_char,
+        amount: u64,
+        error: *mut RawError,
+    ) -> *mut c_void;
+    fn ledger_lean_error_free(error: *mut RawError);
+    fn ledger_lean_balances(state: *mut c_void, entry_fn: EntryFn, ctx: *mut c_void);
+}
+
+// ledger_lean_error in lean_wrapper.c; the tags follow the constructor order
+// of ApplyError in Model.lean, starting at 1
+#[repr(C)]
+struct RawError {
+    tag: u8,
+    account: *mut c_char,
+    account_len: usize,
+    balance: u64,
+    amount: u64,
+}
+
+const ERROR_NONE: u8 = 0;
+const ERROR_INSUFFICIENT_FUNDS: u8 = 1;
+const ERROR_OVERFLOW: u8 = 2;
+
+impl RawError {
+    fn new() -> Self {
+        Self {
+            tag: ERROR_NONE,
+            account: std::ptr::null_mut(),
+            account_len: 0,
+            balance: 0,
+            amount: 0,
+        }
+    }
+
+    fn decode(&self) -> Result<(), ApplyError> {
+        let account = || {
+            let bytes =
+                unsafe { std::slice::from_raw_parts(self.account as *const u8, self.account_len) };
+            String::from_utf8_lossy(bytes).into_owned()
+        };
+        match self.tag {
+            ERROR_NONE => Ok(()),
+            ERROR_INSUFFICIENT_FUNDS => Err(ApplyError::InsufficientFunds {
+                account: account(),
+                balance: self.balance,
+                amount: self.amount,
+            }),
+            ERROR_OVERFLOW => Err(ApplyError::Overflow {
+                account: account(),
+                balance: self.balance,
+                amount: self.amount,
+            }),
+            tag => panic!("unknown ApplyError tag from the Lean model: {}", tag),
+        }
+    }
+}
+
+impl Drop for RawError {
+    fn drop(&mut self) {
+        unsafe { ledger_lean_error_free(self) }
+    }
+}
+
+type EntryFn =
+    extern "C" fn(ctx: *mut c_void, account: *const c_char, account_len: usize, balance: u64);
+
+extern "C" fn collect_entry(
+    ctx: *mut c_void,
+    account: *const c_char,
+    account_len: usize,
+    balance: u64,
+) {
+    let entries = unsafe { &mut *(ctx as *mut Vec<(String, u64)>) };
+    let bytes = unsafe { std::slice::from_raw_parts(account as *const u8, account_len) };
+    entries.push((String::from_utf8_lossy(bytes).into_owned(), balance));
+}
+
+static INIT: Once = Once::new();
+
+/// Initializes the Lean runtime and the model module, once per process. The
+/// thread that gets here first becomes Lean's main thread; call it before
+/// spawning workers.
+pub fn initialize() {
+    INIT.call_once(|| unsafe {
+        ledger_lean_initialize();
+    });
+}
+
+/// Registers the current (non-main) thread with the Lean runtime until the
+/// guard is dropped. A `LeanLedger` must only be created and used on the
+/// thread that made it, inside such a guard unless it is the main thread.
+pub struct LeanThread {
+    // keeps the guard on the thread that registered
+    _not_send: PhantomData<*mut c_void>,
+}
+
+impl LeanThread {
+    pub fn attach() -> Self {
+        assert!(
+            INIT.is_completed(),
+            "the Lean runtime must be initialized before worker threads attach"
+        );
+        unsafe { ledger_lean_initialize_thread() };
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for LeanThread {
+    fn drop(&mut self) {
+        unsafe { ledger_lean_finalize_thread() };
+    }
+}
+
+pub struct LeanLedger {
+    state: *mut c_void,
+}
+
+impl Default for LeanLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LeanLedger {
+    pub fn new() -> Self {
+        initialize();
+        let state = unsafe { ledger_lean_state_new() };
+        Self { state }
+    }
+
+    /// Applies `op` to the model. The outer error means the op never reached
+    /// the model; the inner result is the model's verdict.
+    pub fn apply(&mut self, op: &Op) -> Result<Result<(), ApplyError>, FfiError> {
+        let mut error = RawError::new();
+        let new_state = match op {
+            Op::Deposit { account, amount } => {
+                let account = account_cstring(account)?;
+                unsafe {
+                    ledger_lean_apply_deposit(self.state, account.as_ptr(), *amount, &mut error)
+                }
+            }
+            Op::Withdraw { account, amount } => {
+                let account = account_cstring(account)?;
+                unsafe {
+                    ledger_lean_apply_withdraw(self.state, account.as_ptr(), *amount, &mut error)
+                }
+            }
+            Op::Transfer { from, to, amount } => {
+                let from_account = account_cstring(from)?;
+                let to_account = account_cstring(to)?;
+                unsafe {
+                    ledger_lean_apply_transfer(
+                        self.state,
+                        from_account.as_ptr(),
+                        to_account.as_ptr(),
+                        *amount,
+                        &mut error,
+                    )
+                }
+            }
+        };
+
+        self.state = new_state;
+
+        Ok(error.decode())
+    }
+
+    /// Every balance stored in the model state, sorted by name. The model
+    /// never stores zero balances.
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        let mut items: Vec<(String, u64)> = Vec::new();
+        unsafe {
+            ledger_lean_balances(
+                self.state,
+                collect_entry,
+                &mut items as *mut Vec<(String, u64)> as *mut c_void,
+            );
+        }
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items
+    }
+}
+
+impl Drop for LeanLedger {
+    fn drop(&mut self) {
+        unsafe {
+            ledger_lean_state_dec(self.state);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 #include <lean/lean.h>
 #include <stdint.h>
 #include <stdlib.h>
+#include <string.h>
 
 extern lean_object *verified_ledger_empty;
 extern lean_object *verified_ledger_apply_deposit(lean_object *, lean_object *,
@@ -10,6 +11,7 @@ extern lean_object *verified_ledger_apply_withdraw(lean_object *, lean_object *,
 extern lean_object *verified_ledger_apply_transfer(lean_object *, lean_object *,
                                                    lean_object *, uint64_t);
 extern uint64_t verified_ledger_balance(lean_object *, lean_object *);
+extern lean_object *verified_ledger_balances(lean_object *);
 extern lean_object *initialize_VerifiedLedger_FFI(uint8_t builtin);
 extern void lean_initialize_runtime_module(void);
 extern char **lean_setup_args(int argc, char **argv);
@@ -43,6 +45,12 @@ void ledger_lean_initialize(void) {
   lean_init_task_manager();
 }
 
+// threads not created by Lean must register with the runtime before they
+// touch Lean objects, after ledger_lean_initialize has run on the main thread
+void ledger_lean_initialize_thread(void) { lean_initialize_thread(); }
+
+void ledger_lean_finalize_thread(void) { lean_finalize_thread(); }
+
 void *ledger_lean_state_new(void) {
   lean_inc(verified_ledger_empty);
   return verified_ledger_empty;
@@ -54,43 +62,80 @@ void ledger_lean_state_dec(void *state) {
   }
 }
 
-static void *ledger_lean_apply_result(lean_object *result, uint8_t *ok) {
-  uint8_t ok_value = lean_ctor_get_uint8(result, sizeof(void *) * 1);
-  lean_object *state = lean_ctor_get(result, 0);
-
+/* Mirrors ApplyError in Model.lean; tag 0 means the op succeeded. */
+typedef struct {
+  uint8_t tag;
+  char *account;
+  size_t account_len;
+  uint64_t balance;
+  uint64_t amount;
+} ledger_lean_error;
+
+/* ApplyResult fields: object fields first, then scalars by decreasing size */
+#define APPLY_RESULT_STATE 0
+#define APPLY_RESULT_ERROR_ACCOUNT 1
+#define APPLY_RESULT_SCALARS (sizeof(void *) * 2)
+
+static void *ledger_lean_apply_result(lean_object *result,
+                                      ledger_lean_error *error) {
+  lean_object *state = lean_ctor_get(result, APPLY_RESULT_STATE);
   lean_inc(state);
-  lean_dec_ref(result);
 
-  if (ok != NULL) {
-    *ok = ok_value;
+  if (error != NULL) {
+    error->tag = lean_ctor_get_uint8(result, APPLY_RESULT_SCALARS + 16);
+    error->balance = lean_ctor_get_uint64(result, APPLY_RESULT_SCALARS);
+    error->amount = lean_ctor_get_uint64(result, APPLY_RESULT_SCALARS + 8);
+    error->account = NULL;
+    error->account_len = 0;
+    if (error->tag != 0) {
+      lean_object *account = lean_ctor_get(result, APPLY_RESULT_ERROR_ACCOUNT);
+      /* lean_string_size counts the trailing NUL */
+      size_t len = lean_string_size(account) - 1;
+      error->account = malloc(len + 1);
+      if (error->account == NULL) {
+        abort();
+      }
+      memcpy(error->account, lean_string_cstr(account), len + 1);
+      error->account_len = len;
+    }
   }
+
+  lean_dec_ref(result);
   return state;
 }
 
+void ledger_lean_error_free(ledger_lean_error *error) {
+  if (error != NULL) {
+    free(error->account);
+    error->account = NULL;
+    error->account_len = 0;
+  }
+}
+
 void *ledger_lean_apply_deposit(void *state, const char *account,
-                                uint64_t amount, uint8_t *ok) {
+                                uint64_t amount, ledger_lean_error *error) {
   lean_object *account_obj = lean_mk_string(account);
   lean_object *result =
       verified_ledger_apply_deposit((lean_object *)state, account_obj, amount);
-  return ledger_lean_apply_result(result, ok);
+  return ledger_lean_apply_result(result, error);
 }
 
 void *ledger_lean_apply_withdraw(void *state, const char *account,
-                                 uint64_t amount, uint8_t *ok) {
+                                 uint64_t amount, ledger_lean_error *error) {
   lean_object *account_obj = lean_mk_string(account);
   lean_object *result =
       verified_ledger_apply_withdraw((lean_object *)state, account_obj, amount);
-  return ledger_lean_apply_result(result, ok);
+  return ledger_lean_apply_result(result, error);
 }
 
 void *ledger_lean_apply_transfer(void *state, const char *from_account,
                                  const char *to_account, uint64_t amount,
-                                 uint8_t *ok) {
+                                 ledger_lean_error *error) {
   lean_object *from_obj = lean_mk_string(from_account);
   lean_object *to_obj = lean_mk_string(to_account);
   lean_object *result = verified_ledger_apply_transfer(
       (lean_object *)state, from_obj, to_obj, amount);
-  return ledger_lean_apply_result(result, ok);
+  return ledger_lean_apply_result(result, error);
 }
 
 uint64_t ledger_lean_balance(void *state, const char *account) {
@@ -99,3 +144,22 @@ uint64_t ledger_lean_balance(void *state, const char *account) {
   uint64_t result = verified_ledger_balance((lean_object *)state, account_obj);
   return result;
 }
+
+typedef void (*ledger_lean_entry_fn)(void *ctx, const char *account,
+                                     size_t account_len, uint64_t balance);
+
+void ledger_lean_balances(void *state, ledger_lean_entry_fn entry_fn,
+                          void *ctx) {
+  lean_inc((lean_object *)state);
+  lean_object *entries = verified_ledger_balances((lean_object *)state);
+  size_t count = lean_array_size(entries);
+  for (size_t i = 0; i < count; i++) {
+    lean_object *entry = lean_array_get_core(entries, i);
+    lean_object *account = lean_ctor_get(entry, 0);
+    uint64_t balance = lean_unbox_uint64(lean_ctor_get(entry, 1));
+    /* lean_string_size counts the trailing NUL */
+    entry_fn(ctx, lean_string_cstr(account), lean_string_size(account) - 1,
+             balance);
+  }
+  lean_dec_ref(entries);
+}
@@ -6,6 +6,12 @@ pub struct Ledger {
     balances: HashMap<String, u64>,
 }
 
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ledger {
     pub fn new() -> Self {
         Self {
@@ -33,7 +39,13 @@ impl Ledger {
 
     fn deposit(&mut self, account: &str, amount: u64) -> Result<(), ApplyError> {
         let balance = self.balance(account);
-        let next = balance + amount;
+        let next = balance
+            .checked_add(amount)
+            .ok_or_else(|| ApplyError::Overflow {
+                account: account.to_string(),
+                balance,
+                amount,
+            })?;
         self.set_balance(account, next);
         Ok(())
     }
@@ -71,3 +83,74 @@ impl Ledger {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit(account: &str, amount: u64) -> Op {
+        Op::Deposit {
+            account: account.to_string(),
+            amount,
+        }
+    }
+
+    fn transfer(from: &str, to: &str, amount: u64) -> Op {
+        Op::Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        }
+    }
+
+    fn full(account: &str) -> Vec<(String, u64)> {
+        vec![(account.to_string(), u64::MAX)]
+    }
+
+    #[test]
+    fn deposits_past_u64_max_are_rejected_and_change_nothing() {
+        let mut ledger = Ledger::new();
+        assert_eq!(ledger.apply(&deposit("a", u64::MAX)), Ok(()));
+        assert_eq!(
+            ledger.apply(&deposit("a", 1)),
+            Err(ApplyError::Overflow {
+                account: "a".to_string(),
+                balance: u64::MAX,
+                amount: 1,
+            })
+        );
+        assert_eq!(
+            ledger.apply(&deposit("a", u64::MAX)),
+            Err(ApplyError::Overflow {
+                account: "a".to_string(),
+                balance: u64::MAX,
+                amount: u64::MAX,
+            })
+        );
+        assert_eq!(ledger.snapshot(), full("a"));
+        assert_eq!(ledger.apply(&deposit("a", 0)), Ok(()));
+        assert_eq!(ledger.snapshot(), full("a"));
+    }
+
+    #[test]
+    fn transfers_touching_a_full_account_do_not_overflow() {
+        // the seeded transfer bug never credits `to`, so the amount lands
+        // back in `from` and a full `to` stays as it was
+        let mut ledger = Ledger::new();
+        assert_eq!(ledger.apply(&deposit("full", u64::MAX)), Ok(()));
+        assert_eq!(ledger.apply(&deposit("b", 5)), Ok(()));
+        assert_eq!(ledger.apply(&transfer("b", "full", 3)), Ok(()));
+        assert_eq!(
+            ledger.snapshot(),
+            [("b".to_string(), 5), ("full".to_string(), u64::MAX)]
+        );
+
+        // a self-transfer debits and credits the same account, so even a
+        // full one has room for what it sends
+        let mut ledger = Ledger::new();
+        assert_eq!(ledger.apply(&deposit("a", u64::MAX)), Ok(()));
+        assert_eq!(ledger.apply(&transfer("a", "a", 5)), Ok(()));
+        assert_eq!(ledger.apply(&transfer("a", "a", u64::MAX - 1)), Ok(()));
+        assert_eq!(ledger.snapshot(), full("a"));
+    }
+}
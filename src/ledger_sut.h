@@ -0,0 +1,62 @@
+/* C ABI for implementations loaded with `--sut-lib`. A plugin is a shared
+ * library exporting the functions below; `examples/ledger_plugin.rs` is a
+ * reference plugin wrapping the in-crate Ledger.
+ *
+ * Account names are UTF-8 passed as pointer and length, not NUL-terminated.
+ * The harness only uses a ledger from the thread that created it, but
+ * creates one ledger per worker thread, so separate ledgers must not share
+ * unsynchronized state. Everything here must return normally: a plugin that
+ * crashes takes the harness down with it. */
+
+#ifndef LEDGER_SUT_H
+#define LEDGER_SUT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct ledger_sut ledger_sut;
+
+/* Mirrors ApplyError in Model.lean; tag 0 means the op succeeded. `account`
+ * is owned by the ledger and only needs to stay valid until its next call. */
+typedef struct {
+  uint8_t tag;
+  const char *account;
+  size_t account_len;
+  uint64_t balance;
+  uint64_t amount;
+} ledger_sut_error;
+
+#define LEDGER_SUT_OK 0
+#define LEDGER_SUT_INSUFFICIENT_FUNDS 1
+#define LEDGER_SUT_OVERFLOW 2
+
+/* An empty ledger. */
+ledger_sut *ledger_sut_new(void);
+
+void ledger_sut_free(ledger_sut *ledger);
+
+/* Each apply writes its verdict to `error`. */
+void ledger_sut_apply_deposit(ledger_sut *ledger, const char *account,
+                              size_t account_len, uint64_t amount,
+                              ledger_sut_error *error);
+
+void ledger_sut_apply_withdraw(ledger_sut *ledger, const char *account,
+                               size_t account_len, uint64_t amount,
+                               ledger_sut_error *error);
+
+void ledger_sut_apply_transfer(ledger_sut *ledger, const char *from_account,
+                               size_t from_len, const char *to_account,
+                               size_t to_len, uint64_t amount,
+                               ledger_sut_error *error);
+
+uint64_t ledger_sut_balance(ledger_sut *ledger, const char *account,
+                            size_t account_len);
+
+typedef void (*ledger_sut_entry_fn)(void *ctx, const char *account,
+                                    size_t account_len, uint64_t balance);
+
+/* Calls `entry_fn` once per account with a non-zero balance, in any order. */
+void ledger_sut_list(ledger_sut *ledger, ledger_sut_entry_fn entry_fn,
+                     void *ctx);
+
+#endif
@@ -0,0 +1,14 @@
+pub mod accounts;
+pub mod coverage;
+pub mod emit;
+pub mod fuzz;
+pub mod json;
+pub mod lean_model;
+pub mod ledger;
+pub mod op;
+pub mod reference;
+pub mod report;
+pub mod shrink;
+pub mod sut;
+pub mod trace;
+pub mod triage;
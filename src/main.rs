@@ -1,12 +1,19 @@
-mod fuzz;
-mod lean_model;
-mod ledger;
-mod op;
-
 use std::env;
+use std::fs;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use crate::fuzz::{seed_from_time, FuzzConfig};
+use verified_ledger::accounts::{AccountClass, Accounts};
+use verified_ledger::fuzz::{self, seed_from_time, AmountMix, FuzzConfig, OpWeights};
+use verified_ledger::sut::http::{self as http_sut, LoopbackServer, Templates};
+#[cfg(unix)]
+use verified_ledger::sut::plugin::{Plugin, PluginLedger};
+use verified_ledger::sut::process as process_sut;
+use verified_ledger::sut::Implementation;
+use verified_ledger::{emit, lean_model, report, trace, triage};
 
 fn main() {
     let mut args = env::args().skip(1);
@@ -15,6 +22,7 @@ fn main() {
     match command.as_deref() {
         None | Some("fuzz") => run_fuzz(args, false),
         Some("replay") => run_fuzz(args, true),
+        Some("serve-http") => serve_http(args),
         Some("help") | Some("-h") | Some("--help") => {
             print_help();
         }
@@ -26,12 +34,140 @@ fn main() {
     }
 }
 
+/// Points the run at `--sut-process`, `--sut-http` or `--sut-lib` when
+/// given, after checking it answers a reset or loads.
+fn select_implementation(options: &mut Options) {
+    #[cfg(unix)]
+    if let Some(path) = options.sut_lib.take() {
+        let plugin = match Plugin::load(&path) {
+            Ok(plugin) => plugin,
+            Err(message) => {
+                eprintln!("--sut-lib {}: {}", path.display(), message);
+                process::exit(2);
+            }
+        };
+        options.config.implementation =
+            Implementation::new(move || Box::new(PluginLedger::new(plugin.clone())))
+                .with_replay_args(format!("--sut-lib {:?}", path.display().to_string()));
+        return;
+    }
+    let timeout = options.sut_timeout;
+    let timeout_args = if timeout != process_sut::DEFAULT_TIMEOUT {
+        format!(" --sut-timeout {}s", timeout.as_secs())
+    } else {
+        String::new()
+    };
+    if let Some(command) = options.sut_process.take() {
+        if let Err(message) = process_sut::ProcessLedger::new(command.clone(), timeout).check() {
+            eprintln!("--sut-process {}: {}", command.join(" "), message);
+            process::exit(2);
+        }
+        let args = format!("--sut-process {:?}{}", command.join(" "), timeout_args);
+        options.config.implementation = Implementation::new(move || {
+            Box::new(process_sut::ProcessLedger::new(command.clone(), timeout))
+        })
+        .with_replay_args(args);
+    } else if let Some(target) = options.sut_http.take() {
+        let url = if target == "loopback" {
+            match LoopbackServer::start("127.0.0.1:0") {
+                Ok(server) => server.url(),
+                Err(e) => {
+                    eprintln!("failed to start the loopback server: {}", e);
+                    process::exit(2);
+                }
+            }
+        } else {
+            match target.parse::<http_sut::Url>() {
+                Ok(url) => url,
+                Err(message) => {
+                    eprintln!("invalid value for --sut-http: {}", message);
+                    process::exit(2);
+                }
+            }
+        };
+        let templates = options.sut_http_templates.clone();
+        let mut probe = http_sut::HttpLedger::new(url.clone(), templates.clone(), timeout);
+        if let Err(message) = probe.check() {
+            eprintln!("--sut-http {}: {}", url, message);
+            process::exit(2);
+        }
+        let mut args = format!("--sut-http {}", target);
+        for template in templates.overrides() {
+            args.push_str(&format!(" --sut-http-template {:?}", template));
+        }
+        args.push_str(&timeout_args);
+        options.config.implementation = Implementation::new(move || {
+            Box::new(http_sut::HttpLedger::new(
+                url.clone(),
+                templates.clone(),
+                timeout,
+            ))
+        })
+        .with_replay_args(args);
+    }
+}
+
+/// Serves `Ledger` over HTTP until killed, for trying `--sut-http` against.
+fn serve_http(mut args: impl Iterator<Item = String>) {
+    let mut listen = "127.0.0.1:8080".to_string();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => match args.next() {
+                Some(value) => listen = value,
+                None => {
+                    eprintln!("missing value for --listen");
+                    process::exit(2);
+                }
+            },
+            "-h" | "--help" => {
+                print_help();
+                return;
+            }
+            other => {
+                eprintln!("unknown flag: {}", other);
+                print_help();
+                process::exit(2);
+            }
+        }
+    }
+    let listener = match std::net::TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("failed to listen on {}: {}", listen, e);
+            process::exit(2);
+        }
+    };
+    match listener.local_addr() {
+        Ok(addr) => println!("serving Ledger on http://{}", addr),
+        Err(_) => println!("serving Ledger on http://{}", listen),
+    }
+    http_sut::serve(listener);
+}
+
 fn run_fuzz(args: impl Iterator<Item = String>, replay: bool) {
     let seed = seed_from_time();
     let config = FuzzConfig::new(seed);
 
-    let mut config = match parse_flags(args, config) {
-        Ok(config) => config,
+    let options = Options {
+        config,
+        file: None,
+        trace_dir: Some(PathBuf::from("fuzz-traces")),
+        emit_test: None,
+        emit_lean: None,
+        keep_going: false,
+        expect: None,
+        format: Format::Text,
+        junit: None,
+        cases_given: false,
+        progress: Duration::from_secs(30),
+        sut_process: None,
+        sut_http: None,
+        sut_lib: None,
+        sut_http_templates: Templates::default(),
+        sut_timeout: process_sut::DEFAULT_TIMEOUT,
+    };
+    let mut options = match parse_flags(args, options) {
+        Ok(options) => options,
         Err(ParseOutcome::Help) => {
             print_help();
             return;
@@ -43,24 +179,335 @@ fn run_fuzz(args: impl Iterator<Item = String>, replay: bool) {
         }
     };
 
+    // the emitted test drives the in-crate Ledger, so it would not reproduce
+    // a failure of any other implementation
+    if options.emit_test.is_some()
+        && (options.sut_process.is_some()
+            || options.sut_lib.is_some()
+            || options.sut_http.is_some())
+    {
+        eprintln!("--emit-test writes a test against the in-crate Ledger and cannot be combined with --sut-process, --sut-lib or --sut-http; keep the trace file instead");
+        process::exit(2);
+    }
+    select_implementation(&mut options);
     if replay {
-        config.cases = 1;
+        options.config.cases = 1;
+        options.config.duration = None;
+        options.config.soak = false;
+    }
+    // campaigns run until their time budget or Ctrl-C, not a case count
+    if options.config.is_campaign() && !options.cases_given {
+        options.config.cases = u64::MAX;
+    }
+    // gating needs every signature the run hits, not just the first, and a
+    // soak run should not stop at the first bug it finds
+    if options.expect.is_some() || options.config.soak {
+        options.keep_going = true;
+    }
+    if options.keep_going && (options.emit_test.is_some() || options.emit_lean.is_some()) {
+        eprintln!("--emit-test and --emit-lean need a single failure; replay one of the traces --keep-going writes instead");
+        process::exit(2);
+    }
+    let expected = match &options.expect {
+        Some(path) => match triage::read_expectations(path) {
+            Ok(expected) => Some(expected),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(2);
+            }
+        },
+        None => None,
+    };
+    let text = options.format == Format::Text;
+    if !lean_model::VERIFIED_MODEL {
+        eprintln!("warning: checking against {}", lean_model::ORACLE);
     }
 
+    let report = if let Some(path) = options.file.take() {
+        if !replay {
+            eprintln!("--file is only supported by replay");
+            process::exit(2);
+        }
+        let trace = match trace::read(&path) {
+            Ok(trace) => trace,
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(2);
+            }
+        };
+        if text {
+            println!(
+                "\nreplaying {}: {} initial balances, {} ops",
+                path.display(),
+                trace.initial.len(),
+                trace.ops.len()
+            );
+        }
+        // the trace is already on disk
+        options.trace_dir = None;
+        fuzz::run_trace(&options.config, &trace, &path)
+    } else {
+        let config = &options.config;
+        if text {
+            let cases = match config.cases {
+                u64::MAX => "unlimited".to_string(),
+                cases => cases.to_string(),
+            };
+            let duration = match config.duration {
+                Some(duration) => format!(", duration={}s", duration.as_secs()),
+                None => String::new(),
+            };
+            println!(
+                "\nrunning fuzz: seed={}, cases={}{}, steps={}, max_amount={}, accounts={}, fresh_accounts={}%, op_weights={}, amount_mix={}, swarm={}, directed={}, jobs={}{}",
+                config.seed,
+                cases,
+                duration,
+                config.steps,
+                config.max_amount,
+                config.accounts,
+                config.fresh_accounts,
+                config.op_weights,
+                config.amount_mix,
+                config.swarm,
+                config.directed,
+                config.jobs,
+                if config.soak { ", soak" } else { "" }
+            );
+        }
+        let campaign = config.is_campaign();
+        if campaign {
+            install_sigint_handler();
+        }
+        let control = fuzz::RunControl {
+            stop: &INTERRUPTED,
+            progress_every: campaign.then_some(options.progress),
+            on_progress: &print_progress,
+        };
+        fuzz::run(config, options.keep_going, &control)
+    };
+
+    let buckets = report.buckets.as_deref();
+    let gate = expected
+        .as_deref()
+        .map(|expected| triage::Gate::check(buckets.unwrap_or_default(), expected));
+
+    match options.format {
+        Format::Text => print_text(&report, buckets, gate.as_ref()),
+        Format::Json => println!(
+            "{}",
+            report::json(&options.config, &report, buckets, gate.as_ref())
+        ),
+    }
+    write_outputs(&report, buckets, expected.as_deref(), &options);
+
+    // the oracle itself is in doubt, so no verdict on the implementation
+    // stands, expected or not
+    if report.totals.harness_faults > 0 {
+        eprintln!(
+            "{} case(s) hit a harness fault: the Lean model and the Rust reference disagree, which points at lean_wrapper.c, lean_model.rs or reference.rs rather than the implementation",
+            report.totals.harness_faults
+        );
+        process::exit(3);
+    }
+    let failed = match &gate {
+        Some(gate) => !gate.passed(),
+        None => report.totals.failed > 0,
+    };
+    if failed {
+        process::exit(1);
+    }
+}
+
+// set by the SIGINT handler during campaigns; workers finish the cases they
+// are on and the run reports as usual
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+const SIGINT: c_int = 2;
+const SIG_DFL: usize = 0;
+
+extern "C" {
+    fn signal(signum: c_int, handler: usize) -> usize;
+}
+
+extern "C" fn on_sigint(_: c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    // a second Ctrl-C kills the run outright
+    unsafe {
+        signal(SIGINT, SIG_DFL);
+    }
+}
+
+fn install_sigint_handler() {
+    unsafe {
+        signal(SIGINT, on_sigint as extern "C" fn(c_int) as usize);
+    }
+}
+
+fn print_progress(progress: &fuzz::Progress) {
+    let totals = progress.totals;
+    let secs = progress.elapsed.as_secs_f64();
+    let signatures = match progress.signatures {
+        Some(signatures) => format!(", {} signature(s)", signatures),
+        None => String::new(),
+    };
+    eprintln!(
+        "[{:>6}s] {} cases ({:.1}/s), {} ops, {} failed{}",
+        progress.elapsed.as_secs(),
+        totals.cases,
+        totals.cases as f64 / secs.max(f64::EPSILON),
+        totals.steps,
+        totals.failed,
+        signatures
+    );
+}
+
+fn print_text(
+    report: &fuzz::RunReport,
+    buckets: Option<&[triage::Bucket]>,
+    gate: Option<&triage::Gate>,
+) {
+    let totals = &report.totals;
+    if report.interrupted {
+        println!("\ninterrupted after {}s", report.elapsed.as_secs());
+    }
+    let secs = report.elapsed.as_secs_f64();
     println!(
-        "\nrunning fuzz: seed={}, cases={}, steps={}, max_amount={}",
-        config.seed, config.cases, config.steps, config.max_amount
+        "ran {} cases against {} in {:.1}s ({:.1}/s), {} ops",
+        totals.cases,
+        report.implementation,
+        secs,
+        totals.cases as f64 / secs.max(f64::EPSILON),
+        totals.steps
     );
+    if !lean_model::VERIFIED_MODEL {
+        println!("oracle: {}", lean_model::ORACLE);
+    }
+    if totals.skipped > 0 {
+        println!(
+            "skipped {} of {} ops: account names the Lean FFI cannot carry",
+            totals.skipped,
+            totals.steps + totals.skipped
+        );
+    }
+
+    match buckets {
+        Some([]) => println!("no mismatches found"),
+        Some(buckets) => eprintln!(
+            "{}",
+            triage::Summary {
+                buckets,
+                cases: report.totals.cases,
+            }
+        ),
+        None => match report.failures().next() {
+            Some(failure) => eprintln!("{}", failure),
+            None => println!("no mismatches found"),
+        },
+    }
+
+    print!("{}", totals.coverage);
+
+    if let Some(gate) = gate {
+        println!("{}", gate);
+        if !gate.passed() {
+            eprintln!("new failure signatures; if they are expected, add these lines to the expectations file:");
+            for bucket in &gate.new {
+                eprintln!("{}", bucket.signature);
+            }
+        }
+    }
+}
+
+// traces for every reported failure (the representatives, with --keep-going),
+// the requested regression files for a single failure, and the JUnit report
+fn write_outputs(
+    report: &fuzz::RunReport,
+    buckets: Option<&[triage::Bucket]>,
+    expected: Option<&[triage::Signature]>,
+    options: &Options,
+) {
+    let failures: Vec<&fuzz::FuzzFailure> = match buckets {
+        Some(buckets) => buckets
+            .iter()
+            .map(|bucket| &bucket.representative)
+            .collect(),
+        None => report.failures().collect(),
+    };
+    for failure in &failures {
+        if let Some(dir) = &options.trace_dir {
+            match trace::write_failure(dir, failure) {
+                Ok(paths) => {
+                    for path in paths {
+                        eprintln!("trace written : {}", path.display());
+                        eprintln!("  cargo run -- replay --file {}", path.display());
+                    }
+                }
+                Err(e) => eprintln!("failed to write trace to {}: {}", dir.display(), e),
+            }
+        }
+    }
 
-    match fuzz::run(&config) {
-        Ok(()) => {
-            println!("no mismatches found");
+    if let (Some(failure), None) = (failures.first(), buckets) {
+        let mut sut = options.config.implementation.build();
+        if let Some(path) = &options.emit_test {
+            write_file(path, "test written  ", &emit::rust_test(failure, &mut *sut));
         }
-        Err(failure) => {
-            eprintln!("{}", failure);
-            process::exit(1);
+        if let Some(path) = &options.emit_lean {
+            write_file(
+                path,
+                "lean written  ",
+                &emit::lean_checks(failure, &mut *sut),
+            );
         }
     }
+    if let Some(path) = &options.junit {
+        write_file(path, "junit written ", &report::junit(report, expected));
+    }
+}
+
+fn write_file(path: &Path, label: &str, contents: &str) {
+    match fs::write(path, contents) {
+        Ok(()) => eprintln!("{}: {}", label, path.display()),
+        Err(e) => eprintln!("failed to write {}: {}", path.display(), e),
+    }
+}
+
+struct Options {
+    config: FuzzConfig,
+    /// Trace file to replay instead of generating cases.
+    file: Option<PathBuf>,
+    /// Where failing traces are written, if anywhere.
+    trace_dir: Option<PathBuf>,
+    /// Where to write a Rust regression test for a failure.
+    emit_test: Option<PathBuf>,
+    /// Where to write Lean `#eval` checks for a failure.
+    emit_lean: Option<PathBuf>,
+    /// Run every case and report failures grouped by signature.
+    keep_going: bool,
+    /// Known failure signatures; only new ones fail the run.
+    expect: Option<PathBuf>,
+    format: Format,
+    /// Where to write a JUnit XML report.
+    junit: Option<PathBuf>,
+    /// `--cases` was passed, so a campaign keeps it as a bound.
+    cases_given: bool,
+    /// How often campaigns print a progress line.
+    progress: Duration,
+    /// Program and arguments of an out-of-process implementation.
+    sut_process: Option<Vec<String>>,
+    /// Base URL of an implementation behind HTTP, or `loopback`.
+    sut_http: Option<String>,
+    /// Shared library exporting the plugin ABI of `ledger_sut.h`.
+    sut_lib: Option<PathBuf>,
+    sut_http_templates: Templates,
+    /// How long it gets to answer each request.
+    sut_timeout: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
 }
 
 enum ParseOutcome {
@@ -70,8 +517,9 @@ enum ParseOutcome {
 
 fn parse_flags(
     mut args: impl Iterator<Item = String>,
-    mut config: FuzzConfig,
-) -> Result<FuzzConfig, ParseOutcome> {
+    mut options: Options,
+) -> Result<Options, ParseOutcome> {
+    let config = &mut options.config;
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "--seed" => {
@@ -85,6 +533,7 @@ fn parse_flags(
                     .next()
                     .ok_or_else(|| ParseOutcome::Error("missing value for --cases".to_string()))?;
                 config.cases = parse_u64("--cases", &value)?;
+                options.cases_given = true;
             }
             "--steps" => {
                 let value = args
@@ -98,6 +547,217 @@ fn parse_flags(
                 })?;
                 config.max_amount = parse_u64("--max-amount", &value)?;
             }
+            "--accounts" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --accounts".to_string())
+                })?;
+                let names = value.split(',').map(|name| name.to_string()).collect();
+                config.accounts = Accounts::Fixed(names);
+            }
+            "--account-classes" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --account-classes".to_string())
+                })?;
+                let classes = value
+                    .split(',')
+                    .map(|class| class.trim().parse::<AccountClass>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(ParseOutcome::Error)?;
+                let count = match &config.accounts {
+                    Accounts::Generated { count, .. } => *count,
+                    Accounts::Fixed(_) => 5,
+                };
+                config.accounts = Accounts::Generated { classes, count };
+            }
+            "--account-count" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --account-count".to_string())
+                })?;
+                let count = parse_usize("--account-count", &value)?;
+                if count == 0 {
+                    return Err(ParseOutcome::Error(
+                        "invalid value for --account-count: must be at least 1".to_string(),
+                    ));
+                }
+                config.accounts = match std::mem::take(&mut config.accounts) {
+                    Accounts::Generated { classes, .. } => Accounts::Generated { classes, count },
+                    Accounts::Fixed(_) => Accounts::Generated {
+                        classes: AccountClass::ALL.to_vec(),
+                        count,
+                    },
+                };
+            }
+            "--fresh-accounts" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --fresh-accounts".to_string())
+                })?;
+                config.fresh_accounts = parse_percent("--fresh-accounts", &value)?;
+            }
+            "--op-weights" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --op-weights".to_string())
+                })?;
+                let weights = parse_weights("--op-weights", &value, &OpWeights::KEYS)?;
+                config.op_weights = OpWeights::from_weights(weights);
+            }
+            "--swarm" => {
+                config.swarm = true;
+            }
+            "--directed" => {
+                config.directed = true;
+            }
+            "--amount-mix" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --amount-mix".to_string())
+                })?;
+                let weights = parse_weights("--amount-mix", &value, &AmountMix::KEYS)?;
+                config.amount_mix = AmountMix::from_weights(weights);
+            }
+            "--jobs" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ParseOutcome::Error("missing value for --jobs".to_string()))?;
+                config.jobs = parse_usize("--jobs", &value)?;
+                if config.jobs == 0 {
+                    return Err(ParseOutcome::Error(
+                        "invalid value for --jobs: must be at least 1".to_string(),
+                    ));
+                }
+            }
+            "--duration" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --duration".to_string())
+                })?;
+                config.duration = Some(parse_duration("--duration", &value)?);
+            }
+            "--soak" => {
+                config.soak = true;
+            }
+            "--progress" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --progress".to_string())
+                })?;
+                options.progress = parse_duration("--progress", &value)?;
+            }
+            "--sut-process" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --sut-process".to_string())
+                })?;
+                let command: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+                if command.is_empty() {
+                    return Err(ParseOutcome::Error(
+                        "invalid value for --sut-process: empty command".to_string(),
+                    ));
+                }
+                options.sut_process = Some(command);
+            }
+            "--sut-http" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --sut-http".to_string())
+                })?;
+                options.sut_http = Some(value);
+            }
+            "--sut-http-template" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --sut-http-template".to_string())
+                })?;
+                let invalid = |message: String| {
+                    ParseOutcome::Error(format!(
+                        "invalid value for --sut-http-template: {}",
+                        message
+                    ))
+                };
+                let (key, template) = value
+                    .split_once('=')
+                    .ok_or_else(|| invalid(format!("expected KEY=TEMPLATE, got {:?}", value)))?;
+                let slot = options.sut_http_templates.get_mut(key).ok_or_else(|| {
+                    invalid(format!(
+                        "unknown key {} (expected one of {})",
+                        key,
+                        Templates::KEYS.join(", ")
+                    ))
+                })?;
+                *slot = template.parse().map_err(invalid)?;
+            }
+            #[cfg(unix)]
+            "--sut-lib" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --sut-lib".to_string())
+                })?;
+                options.sut_lib = Some(PathBuf::from(value));
+            }
+            #[cfg(not(unix))]
+            "--sut-lib" => {
+                return Err(ParseOutcome::Error(
+                    "--sut-lib loads libraries with dlopen, which only Unix has".to_string(),
+                ));
+            }
+            "--sut-timeout" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --sut-timeout".to_string())
+                })?;
+                options.sut_timeout = parse_duration("--sut-timeout", &value)?;
+            }
+            "--no-shrink" => {
+                config.shrink = false;
+            }
+            "--keep-going" => {
+                options.keep_going = true;
+            }
+            "--format" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ParseOutcome::Error("missing value for --format".to_string()))?;
+                options.format = match value.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    _ => {
+                        return Err(ParseOutcome::Error(format!(
+                            "invalid value for --format: {} (expected text or json)",
+                            value
+                        )))
+                    }
+                };
+            }
+            "--junit" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ParseOutcome::Error("missing value for --junit".to_string()))?;
+                options.junit = Some(PathBuf::from(value));
+            }
+            "--expect" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ParseOutcome::Error("missing value for --expect".to_string()))?;
+                options.expect = Some(PathBuf::from(value));
+            }
+            "--file" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ParseOutcome::Error("missing value for --file".to_string()))?;
+                options.file = Some(PathBuf::from(value));
+            }
+            "--trace-dir" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --trace-dir".to_string())
+                })?;
+                options.trace_dir = Some(PathBuf::from(value));
+            }
+            "--no-traces" => {
+                options.trace_dir = None;
+            }
+            "--emit-test" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --emit-test".to_string())
+                })?;
+                options.emit_test = Some(PathBuf::from(value));
+            }
+            "--emit-lean" => {
+                let value = args.next().ok_or_else(|| {
+                    ParseOutcome::Error("missing value for --emit-lean".to_string())
+                })?;
+                options.emit_lean = Some(PathBuf::from(value));
+            }
             "--help" | "-h" => {
                 return Err(ParseOutcome::Help);
             }
@@ -107,7 +767,17 @@ fn parse_flags(
         }
     }
 
-    Ok(config)
+    let selected = [
+        options.sut_process.is_some(),
+        options.sut_http.is_some(),
+        options.sut_lib.is_some(),
+    ];
+    if selected.into_iter().filter(|given| *given).count() > 1 {
+        return Err(ParseOutcome::Error(
+            "--sut-process, --sut-http and --sut-lib are mutually exclusive".to_string(),
+        ));
+    }
+    Ok(options)
 }
 
 fn parse_u64(flag: &str, value: &str) -> Result<u64, ParseOutcome> {
@@ -122,16 +792,148 @@ fn parse_usize(flag: &str, value: &str) -> Result<usize, ParseOutcome> {
         .map_err(|_| ParseOutcome::Error(format!("invalid value for {}: {}", flag, value)))
 }
 
+// `90`, `90s`, `10m` or `2h`; a bare number is seconds
+fn parse_duration(flag: &str, value: &str) -> Result<Duration, ParseOutcome> {
+    let invalid = || {
+        ParseOutcome::Error(format!(
+            "invalid value for {}: {} (expected e.g. 30s, 10m or 2h)",
+            flag, value
+        ))
+    };
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+    let secs = match unit {
+        "s" => Some(number),
+        "m" => number.checked_mul(60),
+        "h" => number.checked_mul(60 * 60),
+        _ => None,
+    };
+    match secs {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_percent(flag: &str, value: &str) -> Result<u32, ParseOutcome> {
+    match value.parse::<u32>() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(ParseOutcome::Error(format!(
+            "invalid value for {}: {} (expected 0-100)",
+            flag, value
+        ))),
+    }
+}
+
+// parses `key=weight,...` against a fixed key set; unlisted keys get weight 0
+fn parse_weights<const N: usize>(
+    flag: &str,
+    value: &str,
+    keys: &[&str; N],
+) -> Result<[u32; N], ParseOutcome> {
+    let mut weights = [0u32; N];
+    for part in value.split(',') {
+        let (key, weight) = part
+            .split_once('=')
+            .ok_or_else(|| ParseOutcome::Error(format!("invalid value for {}: {}", flag, part)))?;
+        let index = keys
+            .iter()
+            .position(|candidate| *candidate == key.trim())
+            .ok_or_else(|| {
+                ParseOutcome::Error(format!(
+                    "unknown key for {}: {} (expected one of {})",
+                    flag,
+                    key,
+                    keys.join(", ")
+                ))
+            })?;
+        weights[index] = weight
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| ParseOutcome::Error(format!("invalid value for {}: {}", flag, part)))?;
+    }
+    if weights.iter().all(|weight| *weight == 0) {
+        return Err(ParseOutcome::Error(format!(
+            "invalid value for {}: at least one weight must be non-zero",
+            flag
+        )));
+    }
+    Ok(weights)
+}
+
 fn print_help() {
     println!("verified-ledger: differential fuzzing harness");
     println!("");
     println!("Usage:");
     println!("  cargo run -- fuzz [--seed N] [--cases N] [--steps N] [--max-amount N]");
+    println!(
+        "                   [--accounts A,B,...] [--account-classes C,...] [--account-count N]"
+    );
+    println!("                   [--fresh-accounts PCT]");
+    println!("                   [--op-weights SPEC] [--amount-mix SPEC] [--swarm] [--directed]");
+    println!("                   [--no-shrink] [--jobs N]");
+    println!(
+        "                   [--keep-going] [--trace-dir DIR] [--no-traces] [--emit-test PATH]"
+    );
+    println!("                   [--format text|json] [--expect PATH] [--junit PATH]");
+    println!("                   [--emit-lean PATH] [--duration TIME] [--soak] [--progress TIME]");
+    println!("                   [--sut-process CMD] [--sut-http URL|loopback]");
+    println!("                   [--sut-http-template KEY=TEMPLATE] [--sut-lib PATH]");
+    println!("                   [--sut-timeout TIME]");
+    println!(
+        "  cargo run -- replay --file PATH [--no-shrink] [--emit-test PATH] [--emit-lean PATH]"
+    );
+    println!("  cargo run -- serve-http [--listen ADDR]");
     println!("  cargo run -- replay --seed N [--steps N] [--max-amount N]");
+    println!(
+        "                   [--accounts A,B,...] [--account-classes C,...] [--account-count N]"
+    );
+    println!("                   [--fresh-accounts PCT]");
+    println!("                   [--op-weights SPEC] [--amount-mix SPEC] [--swarm] [--directed]");
+    println!("                   [--no-shrink]");
+    println!("                   [--emit-test PATH] [--emit-lean PATH]");
     println!("");
     println!("Defaults:");
     println!("  --seed       random from system time");
     println!("  --cases      50");
     println!("  --steps      200");
     println!("  --max-amount 50");
+    println!("  --accounts   alice,bob,carol,dave,erin");
+    println!("  --account-classes");
+    println!("               plain,empty,unicode,long,normalization,nul (count defaults to 5)");
+    println!("  --fresh-accounts 2 (percent of account picks that invent a new name)");
+    println!("  --op-weights deposit=45,withdraw=30,transfer=25");
+    println!("  --swarm      off (each case enables a random subset of op kinds and accounts)");
+    println!("  --directed   off (aim about half the ops at the Proofs.lean partitions the case");
+    println!("               has hit least, including self-transfers)");
+    println!("  --amount-mix uniform=60,balance=10,near-balance=10,zero=10,max=5,complement=5");
+    println!("  --no-shrink  off (failures are minimized before reporting)");
+    println!("  --jobs       1 (worker threads; results do not depend on it)");
+    println!("  --duration   none (keep starting cases until e.g. 30s, 10m or 2h have passed;");
+    println!("               --cases becomes unlimited unless given)");
+    println!("  --soak       off (run until Ctrl-C with --keep-going, printing progress; the");
+    println!("               summary is printed on Ctrl-C)");
+    println!("  --progress   30s (progress line interval for --duration and --soak runs)");
+    println!("  --keep-going off (run every case, then summarize failures by signature)");
+    println!("  --format     text (json prints one document with every case and failure)");
+    println!("  --expect     none (known failure signatures; implies --keep-going and only");
+    println!("               new signatures fail the run)");
+    println!("  --junit      none (also write a JUnit XML report to PATH; with --expect, known");
+    println!("               signatures are skipped testcases rather than failures)");
+    println!("  --sut-process none (check a ledger in another process over a JSON line protocol");
+    println!("               instead of Ledger; CMD is split on whitespace)");
+    println!("  --sut-http   none (check a ledger behind an HTTP API at URL, or at a Ledger");
+    println!("               served in-process with `loopback`)");
+    println!("  --sut-http-template");
+    println!("               the /ledgers/{{session}}/... routes of serve-http (KEY is deposit,");
+    println!(
+        "               withdraw, transfer, balances or reset; TEMPLATE is METHOD PATH [BODY])"
+    );
+    println!("  --sut-lib    none (check a ledger in a shared library exporting the C ABI of");
+    println!("               src/ledger_sut.h, loaded with dlopen; Unix only)");
+    println!("  --sut-timeout 5s (how long --sut-process or --sut-http gets to answer each op)");
+    println!("  --listen     127.0.0.1:8080 (serve-http address)");
+    println!("  --trace-dir  fuzz-traces (failing traces are written here; --no-traces to skip)");
 }
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Op {
@@ -24,6 +25,12 @@ pub enum ApplyError {
         balance: u64,
         amount: u64,
     },
+    /// Crediting `amount` would take `account` past `u64::MAX`.
+    Overflow {
+        account: String,
+        balance: u64,
+        amount: u64,
+    },
 }
 
 impl fmt::Display for ApplyError {
@@ -38,6 +45,15 @@ impl fmt::Display for ApplyError {
                 "insufficient funds: account={}, balance={}, amount={}",
                 account, balance, amount
             ),
+            ApplyError::Overflow {
+                account,
+                balance,
+                amount,
+            } => write!(
+                f,
+                "overflow: account={}, balance={}, amount={}",
+                account, balance, amount
+            ),
         }
     }
 }
@@ -46,14 +62,153 @@ impl fmt::Display for Op {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Op::Deposit { account, amount } => {
-                write!(f, "deposit({}, {})", account, amount)
+                write!(f, "deposit({}, {})", AccountName(account), amount)
             }
             Op::Withdraw { account, amount } => {
-                write!(f, "withdraw({}, {})", account, amount)
+                write!(f, "withdraw({}, {})", AccountName(account), amount)
             }
             Op::Transfer { from, to, amount } => {
-                write!(f, "transfer({}, {}, {})", from, to, amount)
+                write!(
+                    f,
+                    "transfer({}, {}, {})",
+                    AccountName(from),
+                    AccountName(to),
+                    amount
+                )
+            }
+        }
+    }
+}
+
+/// Parses the `Display` form back, e.g. `transfer(alice, "b o b", 5)`.
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = parse_call(s)?;
+        match (name, args.as_slice()) {
+            ("deposit", [account, amount]) => Ok(Op::Deposit {
+                account: account.clone(),
+                amount: parse_amount(amount)?,
+            }),
+            ("withdraw", [account, amount]) => Ok(Op::Withdraw {
+                account: account.clone(),
+                amount: parse_amount(amount)?,
+            }),
+            ("transfer", [from, to, amount]) => Ok(Op::Transfer {
+                from: from.clone(),
+                to: to.clone(),
+                amount: parse_amount(amount)?,
+            }),
+            ("deposit" | "withdraw" | "transfer", _) => {
+                Err(format!("wrong number of arguments: {}", s.trim()))
+            }
+            _ => Err(format!("unknown op: {}", name)),
+        }
+    }
+}
+
+/// Displays an account name bare when that is unambiguous, and as a quoted,
+/// escaped string literal otherwise (empty names, spaces, punctuation,
+/// non-ASCII, control characters).
+pub struct AccountName<'a>(pub &'a str);
+
+impl fmt::Display for AccountName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bare = !self.0.is_empty()
+            && self
+                .0
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+        if bare {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{:?}", self.0)
+        }
+    }
+}
+
+/// Splits `name(arg, arg, ...)` into the name and its arguments, unquoting
+/// any argument written as a string literal.
+pub fn parse_call(s: &str) -> Result<(&str, Vec<String>), String> {
+    let s = s.trim();
+    let open = s
+        .find('(')
+        .ok_or_else(|| format!("expected name(args...): {}", s))?;
+    let inner = s[open + 1..]
+        .strip_suffix(')')
+        .ok_or_else(|| format!("missing closing parenthesis: {}", s))?;
+    let name = s[..open].trim();
+
+    let mut args = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let arg = if chars.next_if_eq(&'"').is_some() {
+            parse_quoted(&mut chars)?
+        } else {
+            let mut arg = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                arg.push(c);
             }
+            arg.trim_end().to_string()
+        };
+        args.push(arg);
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some(',') => continue,
+            Some(c) => return Err(format!("unexpected '{}' in: {}", c, s)),
         }
     }
+    if args.len() == 1 && args[0].is_empty() && !inner.contains('"') {
+        args.clear();
+    }
+
+    Ok((name, args))
+}
+
+// the escapes `{:?}` produces for str: \0 \t \r \n \\ \' \" and \u{..}
+fn parse_quoted(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unterminated string literal".to_string()),
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('0') => out.push('\0'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('n') => out.push('\n'),
+                Some('\\') => out.push('\\'),
+                Some('\'') => out.push('\''),
+                Some('"') => out.push('"'),
+                Some('u') => {
+                    if chars.next() != Some('{') {
+                        return Err("expected '{' after \\u".to_string());
+                    }
+                    let mut hex = String::new();
+                    while let Some(c) = chars.next_if(|c| *c != '}') {
+                        hex.push(c);
+                    }
+                    chars.next();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("invalid unicode escape: \\u{{{}}}", hex))?;
+                    out.push(c);
+                }
+                Some(c) => return Err(format!("unknown escape: \\{}", c)),
+                None => return Err("unterminated string literal".to_string()),
+            },
+            Some(c) => out.push(c),
+        }
+    }
+}
+
+pub fn parse_amount(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("invalid amount: {}", s.trim()))
 }
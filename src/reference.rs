@@ -0,0 +1,221 @@
+use crate::op::{ApplyError, Op};
+
+/// A line-by-line Rust transcription of `Model.lean`, run next to
+/// `LeanLedger` so a disagreement between the two points at the FFI glue
+/// (`lean_wrapper.c`, `lean_model.rs`) instead of at the implementation.
+///
+/// It keeps the model's representation on purpose: an association list
+/// with the newest entry first, `UInt64` arithmetic that wraps, and the
+/// overflow check done on unbounded naturals.
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceModel {
+    balances: Vec<(String, u64)>,
+}
+
+impl ReferenceModel {
+    /// `empty`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `balance`: the first entry for `account`, or 0.
+    pub fn balance(&self, account: &str) -> u64 {
+        self.balances
+            .iter()
+            .find(|entry| entry.0 == account)
+            .map_or(0, |entry| entry.1)
+    }
+
+    /// `setBalance`: drops every entry for `account`, then conses the new
+    /// one unless it is 0.
+    fn set_balance(&mut self, account: &str, amount: u64) {
+        self.balances.retain(|entry| entry.0 != account);
+        if amount != 0 {
+            self.balances.insert(0, (account.to_string(), amount));
+        }
+    }
+
+    /// `apply`. The state is only replaced on `Except.ok`.
+    pub fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        match op {
+            Op::Deposit { account, amount } => {
+                let current = self.balance(account);
+                if add_overflows(current, *amount) {
+                    Err(ApplyError::Overflow {
+                        account: account.clone(),
+                        balance: current,
+                        amount: *amount,
+                    })
+                } else {
+                    self.set_balance(account, current.wrapping_add(*amount));
+                    Ok(())
+                }
+            }
+            Op::Withdraw { account, amount } => {
+                let current = self.balance(account);
+                if current < *amount {
+                    Err(ApplyError::InsufficientFunds {
+                        account: account.clone(),
+                        balance: current,
+                        amount: *amount,
+                    })
+                } else {
+                    self.set_balance(account, current.wrapping_sub(*amount));
+                    Ok(())
+                }
+            }
+            Op::Transfer { from, to, amount } => {
+                let current = self.balance(from);
+                if current < *amount {
+                    Err(ApplyError::InsufficientFunds {
+                        account: from.clone(),
+                        balance: current,
+                        amount: *amount,
+                    })
+                } else if from != to && add_overflows(self.balance(to), *amount) {
+                    Err(ApplyError::Overflow {
+                        account: to.clone(),
+                        balance: self.balance(to),
+                        amount: *amount,
+                    })
+                } else {
+                    self.set_balance(from, current.wrapping_sub(*amount));
+                    let to_balance = self.balance(to);
+                    self.set_balance(to, to_balance.wrapping_add(*amount));
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Every stored balance, sorted by name, as `LeanLedger::snapshot`
+    /// reports them.
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        let mut items = self.balances.clone();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items
+    }
+}
+
+/// `addOverflows`: `UInt64.size ≤ a.toNat + b.toNat`.
+fn add_overflows(a: u64, b: u64) -> bool {
+    1u128 << 64 <= a as u128 + b as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit(account: &str, amount: u64) -> Op {
+        Op::Deposit {
+            account: account.to_string(),
+            amount,
+        }
+    }
+
+    fn withdraw(account: &str, amount: u64) -> Op {
+        Op::Withdraw {
+            account: account.to_string(),
+            amount,
+        }
+    }
+
+    fn transfer(from: &str, to: &str, amount: u64) -> Op {
+        Op::Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+        }
+    }
+
+    fn balances(entries: &[(&str, u64)]) -> Vec<(String, u64)> {
+        entries
+            .iter()
+            .map(|(account, balance)| (account.to_string(), *balance))
+            .collect()
+    }
+
+    #[test]
+    fn overflowing_deposits_and_transfers_are_rejected_and_change_nothing() {
+        let mut model = ReferenceModel::new();
+        assert_eq!(model.apply(&deposit("full", u64::MAX)), Ok(()));
+        assert_eq!(model.apply(&deposit("b", 5)), Ok(()));
+        assert_eq!(
+            model.apply(&deposit("full", 1)),
+            Err(ApplyError::Overflow {
+                account: "full".to_string(),
+                balance: u64::MAX,
+                amount: 1,
+            })
+        );
+        assert_eq!(
+            model.apply(&transfer("b", "full", 3)),
+            Err(ApplyError::Overflow {
+                account: "full".to_string(),
+                balance: u64::MAX,
+                amount: 3,
+            })
+        );
+        assert_eq!(model.snapshot(), balances(&[("b", 5), ("full", u64::MAX)]));
+        // a zero amount never overflows
+        assert_eq!(model.apply(&transfer("b", "full", 0)), Ok(()));
+        assert_eq!(model.apply(&deposit("full", 0)), Ok(()));
+        assert_eq!(model.snapshot(), balances(&[("b", 5), ("full", u64::MAX)]));
+    }
+
+    #[test]
+    fn overdrawing_withdrawals_and_transfers_are_rejected_and_change_nothing() {
+        let mut model = ReferenceModel::new();
+        assert_eq!(model.apply(&deposit("a", 5)), Ok(()));
+        assert_eq!(
+            model.apply(&withdraw("a", 6)),
+            Err(ApplyError::InsufficientFunds {
+                account: "a".to_string(),
+                balance: 5,
+                amount: 6,
+            })
+        );
+        assert_eq!(
+            model.apply(&transfer("nobody", "a", 1)),
+            Err(ApplyError::InsufficientFunds {
+                account: "nobody".to_string(),
+                balance: 0,
+                amount: 1,
+            })
+        );
+        assert_eq!(model.snapshot(), balances(&[("a", 5)]));
+        // emptying an account drops it from the snapshot
+        assert_eq!(model.apply(&withdraw("a", 5)), Ok(()));
+        assert_eq!(model.snapshot(), []);
+    }
+
+    #[test]
+    fn self_transfers_leave_the_balance_alone_even_when_full() {
+        let mut model = ReferenceModel::new();
+        assert_eq!(model.apply(&deposit("a", 5)), Ok(()));
+        assert_eq!(model.apply(&transfer("a", "a", 5)), Ok(()));
+        assert_eq!(
+            model.apply(&transfer("a", "a", 6)),
+            Err(ApplyError::InsufficientFunds {
+                account: "a".to_string(),
+                balance: 5,
+                amount: 6,
+            })
+        );
+        assert_eq!(model.snapshot(), balances(&[("a", 5)]));
+
+        assert_eq!(model.apply(&deposit("full", u64::MAX)), Ok(()));
+        assert_eq!(model.apply(&transfer("full", "full", u64::MAX)), Ok(()));
+        assert_eq!(model.snapshot(), balances(&[("a", 5), ("full", u64::MAX)]));
+    }
+
+    #[test]
+    fn transfers_move_the_amount() {
+        let mut model = ReferenceModel::new();
+        assert_eq!(model.apply(&deposit("a", 5)), Ok(()));
+        assert_eq!(model.apply(&transfer("a", "b", 3)), Ok(()));
+        assert_eq!(model.snapshot(), balances(&[("a", 2), ("b", 3)]));
+        assert_eq!(model.balance("b"), 3);
+        assert_eq!(model.balance("nobody"), 0);
+    }
+}
@@ -0,0 +1,552 @@
+use std::time::Duration;
+
+use crate::coverage::{Class, Coverage};
+use crate::fuzz::{CaseResult, FuzzConfig, FuzzFailure, ImplOutcome, RunReport, StepRecord, Trace};
+use crate::json::Json;
+use crate::lean_model;
+use crate::op::{ApplyError, Op};
+use crate::triage::{Bucket, Gate, Signature};
+
+/// The whole run as one JSON document for `--format json`: the config, a
+/// summary, every case the run kept with its failure and full history, and
+/// the signature buckets and expectation verdicts when the run kept going.
+pub fn json(
+    config: &FuzzConfig,
+    report: &RunReport,
+    buckets: Option<&[Bucket]>,
+    gate: Option<&Gate>,
+) -> Json {
+    let totals = &report.totals;
+    let mut fields = vec![
+        ("config", config_json(config)),
+        ("implementation", Json::string(&report.implementation)),
+        ("oracle", Json::string(lean_model::ORACLE)),
+        ("verified_model", lean_model::VERIFIED_MODEL.into()),
+        ("elapsed_secs", secs(report.elapsed)),
+        ("interrupted", report.interrupted.into()),
+        (
+            "summary",
+            Json::object([
+                ("cases", totals.cases.into()),
+                ("passed", (totals.cases - totals.failed).into()),
+                ("failed", totals.failed.into()),
+                ("harness_faults", totals.harness_faults.into()),
+                ("steps", totals.steps.into()),
+                ("skipped", totals.skipped.into()),
+            ]),
+        ),
+        ("coverage", coverage_json(&totals.coverage)),
+        (
+            "cases",
+            Json::Array(report.cases.iter().map(case_json).collect()),
+        ),
+    ];
+    if let Some(buckets) = buckets {
+        fields.push((
+            "signatures",
+            Json::Array(buckets.iter().map(bucket_json).collect()),
+        ));
+    }
+    if let Some(gate) = gate {
+        let signatures = |signatures: Vec<&Signature>| {
+            Json::Array(
+                signatures
+                    .into_iter()
+                    .map(|signature| Json::string(signature.to_string()))
+                    .collect(),
+            )
+        };
+        fields.push((
+            "expectations",
+            Json::object([
+                ("passed", gate.passed().into()),
+                (
+                    "known",
+                    signatures(gate.known.iter().map(|bucket| &bucket.signature).collect()),
+                ),
+                (
+                    "new",
+                    signatures(gate.new.iter().map(|bucket| &bucket.signature).collect()),
+                ),
+                ("not_hit", signatures(gate.not_hit.clone())),
+            ]),
+        ));
+    }
+    Json::object(fields)
+}
+
+fn config_json(config: &FuzzConfig) -> Json {
+    Json::object([
+        ("seed", config.seed.into()),
+        // campaigns with no case bound
+        (
+            "cases",
+            (config.cases != u64::MAX).then_some(config.cases).into(),
+        ),
+        ("steps", config.steps.into()),
+        ("max_amount", config.max_amount.into()),
+        ("accounts", Json::string(config.accounts.to_string())),
+        ("fresh_accounts", (config.fresh_accounts as u64).into()),
+        ("op_weights", Json::string(config.op_weights.to_string())),
+        ("amount_mix", Json::string(config.amount_mix.to_string())),
+        ("swarm", config.swarm.into()),
+        ("directed", config.directed.into()),
+        ("shrink", config.shrink.into()),
+        ("jobs", config.jobs.into()),
+        (
+            "duration_secs",
+            config.duration.map(|duration| duration.as_secs()).into(),
+        ),
+        ("soak", config.soak.into()),
+    ])
+}
+
+// one entry per class, in `Class::ALL` order
+fn coverage_json(coverage: &Coverage) -> Json {
+    Json::Array(
+        Class::ALL
+            .into_iter()
+            .map(|class| {
+                Json::object([
+                    ("theorem", Json::string(class.theorem())),
+                    ("condition", Json::string(class.condition())),
+                    ("steps", coverage.count(class).into()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn case_json(case: &CaseResult) -> Json {
+    let mut fields = vec![
+        ("case", case.case_index.into()),
+        ("seed", case.seed.into()),
+        ("elapsed_secs", secs(case.elapsed)),
+    ];
+    match &case.outcome {
+        Ok(stats) => {
+            fields.push(("status", Json::string("passed")));
+            fields.push(("steps", stats.steps.into()));
+            fields.push(("skipped", stats.skipped.into()));
+        }
+        Err(failure) => {
+            fields.push(("status", Json::string("failed")));
+            fields.push(("steps", failure.history.len().into()));
+            fields.push(("failure", failure_json(failure)));
+        }
+    }
+    Json::object(fields)
+}
+
+fn bucket_json(bucket: &Bucket) -> Json {
+    let failure = &bucket.representative;
+    Json::object([
+        ("signature", Json::string(bucket.signature.to_string())),
+        ("count", bucket.count.into()),
+        ("representative_case", failure.case_index.into()),
+        ("replay", Json::string(&failure.replay_args)),
+        (
+            "minimized",
+            failure.minimized.as_ref().map_or(Json::Null, trace_json),
+        ),
+    ])
+}
+
+fn failure_json(failure: &FuzzFailure) -> Json {
+    Json::object([
+        (
+            "signature",
+            Json::string(Signature::of(failure).to_string()),
+        ),
+        ("seed", failure.seed.into()),
+        ("case", failure.case_index.into()),
+        ("step", failure.step_index.into()),
+        ("op", op_json(&failure.op)),
+        ("model_result", result_json(&failure.model_result)),
+        ("impl_outcome", outcome_json(&failure.impl_outcome)),
+        (
+            "model_pre_snapshot",
+            balances_json(&failure.model_pre_snapshot),
+        ),
+        (
+            "impl_pre_snapshot",
+            balances_json(&failure.impl_pre_snapshot),
+        ),
+        ("initial", balances_json(&failure.initial)),
+        (
+            "history",
+            Json::Array(failure.history.iter().map(step_json).collect()),
+        ),
+        (
+            "minimized",
+            failure.minimized.as_ref().map_or(Json::Null, trace_json),
+        ),
+        ("replay", Json::string(&failure.replay_args)),
+        (
+            "harness_fault",
+            failure.harness_fault.as_ref().map_or(Json::Null, |fault| {
+                Json::object([
+                    ("reference_result", result_json(&fault.reference_result)),
+                    (
+                        "reference_snapshot",
+                        balances_json(&fault.reference_snapshot),
+                    ),
+                ])
+            }),
+        ),
+    ])
+}
+
+fn step_json(step: &StepRecord) -> Json {
+    Json::object([
+        ("op", op_json(&step.op)),
+        ("model_result", result_json(&step.model_result)),
+        ("impl_outcome", outcome_json(&step.impl_outcome)),
+        ("model_snapshot", balances_json(&step.model_snapshot)),
+        ("impl_snapshot", balances_json(&step.impl_snapshot)),
+    ])
+}
+
+fn trace_json(trace: &Trace) -> Json {
+    Json::object([
+        ("initial", balances_json(&trace.initial)),
+        ("ops", Json::Array(trace.ops.iter().map(op_json).collect())),
+    ])
+}
+
+pub(crate) fn op_json(op: &Op) -> Json {
+    match op {
+        Op::Deposit { account, amount } => Json::object([
+            ("kind", Json::string("deposit")),
+            ("account", Json::string(account)),
+            ("amount", (*amount).into()),
+        ]),
+        Op::Withdraw { account, amount } => Json::object([
+            ("kind", Json::string("withdraw")),
+            ("account", Json::string(account)),
+            ("amount", (*amount).into()),
+        ]),
+        Op::Transfer { from, to, amount } => Json::object([
+            ("kind", Json::string("transfer")),
+            ("from", Json::string(from)),
+            ("to", Json::string(to)),
+            ("amount", (*amount).into()),
+        ]),
+    }
+}
+
+// `{"ok": true}`, or the error variant with its fields
+pub(crate) fn result_json(result: &Result<(), ApplyError>) -> Json {
+    let (error, account, balance, amount) = match result {
+        Ok(()) => return Json::object([("ok", true.into())]),
+        Err(ApplyError::InsufficientFunds {
+            account,
+            balance,
+            amount,
+        }) => ("insufficient-funds", account, balance, amount),
+        Err(ApplyError::Overflow {
+            account,
+            balance,
+            amount,
+        }) => ("overflow", account, balance, amount),
+    };
+    Json::object([
+        ("ok", false.into()),
+        ("error", Json::string(error)),
+        ("account", Json::string(account)),
+        ("balance", (*balance).into()),
+        ("amount", (*amount).into()),
+    ])
+}
+
+fn outcome_json(outcome: &ImplOutcome) -> Json {
+    match outcome {
+        ImplOutcome::Returned(result) => result_json(result),
+        ImplOutcome::Panicked(message) => {
+            Json::object([("ok", false.into()), ("panic", Json::string(message))])
+        }
+    }
+}
+
+// `[[account, balance], ...]`, in snapshot order
+pub(crate) fn balances_json(balances: &[(String, u64)]) -> Json {
+    Json::Array(
+        balances
+            .iter()
+            .map(|(account, balance)| Json::Array(vec![Json::string(account), (*balance).into()]))
+            .collect(),
+    )
+}
+
+fn secs(duration: Duration) -> Json {
+    Json::Float(duration.as_secs_f64())
+}
+
+/// A JUnit XML report with one testcase per fuzz case. Failures whose
+/// signature is in `expected` are reported as skipped, so a gated run shows
+/// them without failing the suite.
+///
+/// Keep-going campaigns do not keep their cases, so they get one testcase per
+/// signature instead.
+pub fn junit(report: &RunReport, expected: Option<&[Signature]>) -> String {
+    let is_known = |failure: &FuzzFailure| {
+        expected.is_some_and(|expected| expected.contains(&Signature::of(failure)))
+    };
+    let testcases: Vec<(String, Duration, Option<&FuzzFailure>)> = match &report.buckets {
+        Some(buckets) if report.cases.is_empty() => buckets
+            .iter()
+            .map(|bucket| {
+                (
+                    format!("{} ({} cases)", bucket.signature, bucket.count),
+                    Duration::ZERO,
+                    Some(&bucket.representative),
+                )
+            })
+            .collect(),
+        _ => report
+            .cases
+            .iter()
+            .map(|case| {
+                (
+                    format!("case {} (seed {})", case.case_index, case.seed),
+                    case.elapsed,
+                    case.outcome.as_ref().err(),
+                )
+            })
+            .collect(),
+    };
+    let failures = testcases
+        .iter()
+        .filter(|(_, _, failure)| failure.is_some_and(|failure| !is_known(failure)))
+        .count();
+    let skipped = testcases
+        .iter()
+        .filter(|(_, _, failure)| failure.is_some_and(is_known))
+        .count();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        testcases.len(),
+        failures,
+        skipped,
+        report.elapsed.as_secs_f64()
+    ));
+    out.push_str(&format!(
+        "  <testsuite name=\"verified-ledger fuzz\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        testcases.len(),
+        failures,
+        skipped,
+        report.elapsed.as_secs_f64()
+    ));
+    for (name, elapsed, failure) in &testcases {
+        out.push_str(&format!(
+            "    <testcase classname=\"verified-ledger.fuzz\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(name),
+            elapsed.as_secs_f64()
+        ));
+        match failure {
+            None => out.push_str("/>\n"),
+            Some(failure) => {
+                let signature = Signature::of(failure);
+                out.push_str(">\n");
+                if is_known(failure) {
+                    out.push_str(&format!(
+                        "      <skipped message=\"known failure: {}\"/>\n",
+                        xml_escape(&signature.to_string())
+                    ));
+                } else {
+                    out.push_str(&format!(
+                        "      <failure message=\"{}\" type=\"mismatch\">{}</failure>\n",
+                        xml_escape(&signature.to_string()),
+                        xml_escape(&failure.to_string())
+                    ));
+                }
+                out.push_str("    </testcase>\n");
+            }
+        }
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+// XML 1.0 cannot carry most control characters even as references, so those
+// are spelled out the way Rust's `{:?}` would
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' | '\r' | '\t' => out.push(c),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::RunStats;
+    use crate::triage;
+
+    fn failure(case_index: u64, op: Op) -> FuzzFailure {
+        let account = match &op {
+            Op::Deposit { account, .. } | Op::Withdraw { account, .. } => account.clone(),
+            Op::Transfer { from, .. } => from.clone(),
+        };
+        FuzzFailure {
+            seed: case_index,
+            case_index,
+            step_index: 0,
+            op,
+            model_result: Ok(()),
+            impl_outcome: ImplOutcome::Returned(Err(ApplyError::InsufficientFunds {
+                account,
+                balance: 0,
+                amount: 1,
+            })),
+            model_pre_snapshot: Vec::new(),
+            impl_pre_snapshot: Vec::new(),
+            initial: Vec::new(),
+            history: Vec::new(),
+            minimized: None,
+            replay_args: format!("--seed {}", case_index),
+            harness_fault: None,
+        }
+    }
+
+    fn case(case_index: u64, outcome: Result<RunStats, FuzzFailure>) -> CaseResult {
+        CaseResult {
+            case_index,
+            seed: case_index,
+            elapsed: Duration::from_millis(5),
+            outcome,
+        }
+    }
+
+    fn report() -> RunReport {
+        let withdraw = Op::Withdraw {
+            account: "alice".to_string(),
+            amount: 1,
+        };
+        let deposit = Op::Deposit {
+            account: "<b>\u{1}".to_string(),
+            amount: 1,
+        };
+        RunReport {
+            cases: vec![
+                case(0, Ok(RunStats::default())),
+                case(1, Err(failure(1, withdraw))),
+                case(2, Err(failure(2, deposit))),
+            ],
+            totals: RunStats {
+                cases: 3,
+                failed: 2,
+                ..RunStats::default()
+            },
+            ..RunReport::default()
+        }
+    }
+
+    #[test]
+    fn junit_skips_known_signatures_and_fails_new_ones() {
+        let report = report();
+        let known = [Signature::of(report.failures().next().expect("a failure"))];
+
+        let xml = junit(&report, Some(&known));
+        assert!(
+            xml.contains("<testsuites tests=\"3\" failures=\"1\" skipped=\"1\""),
+            "{}",
+            xml
+        );
+        assert_eq!(xml.matches("<testcase ").count(), 3);
+        assert_eq!(
+            xml.matches("<skipped message=\"known failure: withdraw ")
+                .count(),
+            1
+        );
+        assert_eq!(xml.matches("<failure message=\"deposit ").count(), 1);
+        assert!(xml.contains("&lt;b&gt;\\u{1}"), "{}", xml);
+        assert!(!xml.contains('\u{1}'));
+
+        let xml = junit(&report, None);
+        assert!(
+            xml.contains("<testsuites tests=\"3\" failures=\"2\" skipped=\"0\""),
+            "{}",
+            xml
+        );
+
+        // campaigns keep no cases, so each signature is a testcase
+        let buckets = triage::dedup(report.failures());
+        let campaign = RunReport {
+            buckets: Some(buckets),
+            ..RunReport::default()
+        };
+        let xml = junit(&campaign, Some(&known));
+        assert!(
+            xml.contains("<testsuites tests=\"2\" failures=\"1\" skipped=\"1\""),
+            "{}",
+            xml
+        );
+        assert!(xml.contains("(1 cases)"), "{}", xml);
+    }
+
+    #[test]
+    fn xml_escape_spells_out_control_characters() {
+        assert_eq!(
+            xml_escape("a\0b\u{1b}<&>\"'\t\r\n\u{7f}\u{85}zoë"),
+            "a\\u{0}b\\u{1b}&lt;&amp;&gt;&quot;&apos;\t\r\n\\u{7f}\\u{85}zoë"
+        );
+        assert_eq!(xml_escape(""), "");
+    }
+
+    #[test]
+    fn json_reports_cases_and_expectations() {
+        let report = report();
+        let buckets = triage::dedup(report.failures());
+        let expected: Vec<Signature> = [
+            Signature::of(&buckets[0].representative),
+            "transfer model=ok impl=ok mismatch=state"
+                .parse()
+                .expect("signature parses"),
+        ]
+        .to_vec();
+        let gate = Gate::check(&buckets, &expected);
+        let text = json(&FuzzConfig::new(1), &report, Some(&buckets), Some(&gate)).to_string();
+        let json: Json = text.parse().expect("the report is valid JSON");
+
+        let summary = json.get("summary").expect("a summary");
+        assert_eq!(summary.get("cases").and_then(Json::as_u64), Some(3));
+        assert_eq!(summary.get("failed").and_then(Json::as_u64), Some(2));
+        let cases = json.get("cases").and_then(Json::as_array).expect("cases");
+        assert_eq!(cases.len(), 3);
+        assert_eq!(
+            cases[2]
+                .get("failure")
+                .and_then(|failure| failure.get("replay"))
+                .and_then(Json::as_str),
+            Some("--seed 2")
+        );
+        let expectations = json.get("expectations").expect("expectations");
+        assert_eq!(
+            expectations.get("passed").and_then(Json::as_bool),
+            Some(false)
+        );
+        let listed = |key: &str| {
+            expectations
+                .get(key)
+                .and_then(Json::as_array)
+                .map_or(0, <[Json]>::len)
+        };
+        assert_eq!(
+            (listed("known"), listed("new"), listed("not_hit")),
+            (1, 1, 1)
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use crate::fuzz::{self, FuzzFailure, Trace};
+use crate::op::Op;
+use crate::sut::LedgerUnderTest;
+use crate::triage::Signature;
+
+// upper bound on shrink rounds; each round only ever makes the trace smaller,
+// this just caps the replay count on pathological histories
+const MAX_ROUNDS: usize = 8;
+
+/// Minimizes the history of `failure` to a small trace that still makes the
+/// model and `sut` diverge.
+///
+/// The ops before the divergence are folded into the initial balances where
+/// possible, and the op sequence is delta-debugged; the remaining accounts are
+/// then renamed to the fewest short names, and amounts and initial balances
+/// are lowered toward 0 or 1. Rounds repeat until nothing changes.
+pub fn shrink(failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> Trace {
+    let mut trace = Trace {
+        initial: failure.initial.clone(),
+        ops: failure.history.iter().map(|step| step.op.clone()).collect(),
+    };
+
+    for _ in 0..MAX_ROUNDS {
+        let before = trace.clone();
+        trace = fold_prefix(trace, failure, sut);
+        trace = shrink_ops(trace, failure, sut);
+        trace = shrink_accounts(trace, failure, sut);
+        trace = shrink_amounts(trace, failure, sut);
+        if trace == before {
+            break;
+        }
+    }
+
+    trace
+}
+
+// model and implementation agree on every state before the divergent step, so
+// the ops leading up to it can usually be replaced by seeding that state
+fn fold_prefix(trace: Trace, failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> Trace {
+    let divergent = match fuzz::replay_trace(&trace, failure.seed, failure.case_index, sut) {
+        Ok(()) => return trace,
+        Err(divergent) => divergent,
+    };
+    if divergent.step_index == 0 {
+        return trace;
+    }
+
+    let candidate = Trace {
+        initial: divergent.model_pre_snapshot,
+        ops: vec![divergent.op],
+    };
+    if diverges(&candidate, failure, sut) {
+        candidate
+    } else {
+        trace
+    }
+}
+
+fn shrink_ops(trace: Trace, failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> Trace {
+    let ops = minimize_ops(&trace.ops, |candidate| {
+        let candidate = Trace {
+            initial: trace.initial.clone(),
+            ops: candidate.to_vec(),
+        };
+        diverges(&candidate, failure, sut)
+    });
+    Trace {
+        initial: trace.initial,
+        ops,
+    }
+}
+
+// renames accounts in order of appearance, preferring to merge an account into
+// one already renamed and otherwise giving it the next unused short name
+fn shrink_accounts(
+    mut trace: Trace,
+    failure: &FuzzFailure,
+    sut: &mut dyn LedgerUnderTest,
+) -> Trace {
+    let mut renamed: Vec<String> = Vec::new();
+
+    for account in trace.accounts() {
+        if renamed.contains(&account) {
+            continue;
+        }
+
+        let merged = renamed.iter().find_map(|target| {
+            let candidate = rename_account(&trace, &account, target);
+            diverges(&candidate, failure, sut).then_some(candidate)
+        });
+        if let Some(candidate) = merged {
+            trace = candidate;
+            continue;
+        }
+
+        let fresh = short_name(&trace, &account, &renamed);
+        if fresh != account {
+            let candidate = rename_account(&trace, &account, &fresh);
+            if !diverges(&candidate, failure, sut) {
+                renamed.push(account);
+                continue;
+            }
+            trace = candidate;
+        }
+        renamed.push(fresh);
+    }
+
+    trace
+}
+
+fn shrink_amounts(mut trace: Trace, failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> Trace {
+    // equal amounts and balances usually matter together (an exact-balance
+    // withdraw stops failing as soon as either side moves), so lower every
+    // occurrence of a value at once before touching them one by one
+    let mut values: Vec<u64> = trace
+        .ops
+        .iter()
+        .map(op_amount)
+        .chain(trace.initial.iter().map(|(_, balance)| *balance))
+        .collect();
+    values.sort_unstable();
+    values.dedup();
+    for value in values.into_iter().rev() {
+        let lowered = minimize_value(value, |candidate| {
+            diverges(&replace_value(&trace, value, candidate), failure, sut)
+        });
+        if lowered != value {
+            trace = replace_value(&trace, value, lowered);
+        }
+    }
+
+    for index in 0..trace.ops.len() {
+        let amount = op_amount(&trace.ops[index]);
+        let lowered = minimize_value(amount, |value| {
+            let mut candidate = trace.clone();
+            set_op_amount(&mut candidate.ops[index], value);
+            diverges(&candidate, failure, sut)
+        });
+        set_op_amount(&mut trace.ops[index], lowered);
+    }
+
+    // a zero initial balance is the same as no entry, so lowering to 0 drops it
+    let mut index = 0;
+    while index < trace.initial.len() {
+        let balance = trace.initial[index].1;
+        let lowered = minimize_value(balance, |value| {
+            diverges(&with_initial_balance(&trace, index, value), failure, sut)
+        });
+        trace = with_initial_balance(&trace, index, lowered);
+        if lowered != 0 {
+            index += 1;
+        }
+    }
+
+    trace
+}
+
+// tries 0 and 1 first, then binary searches for the smallest value that still
+// fails, assuming failure is roughly monotone in the value
+fn minimize_value(value: u64, mut fails: impl FnMut(u64) -> bool) -> u64 {
+    if value == 0 || fails(0) {
+        return 0;
+    }
+    if value == 1 || fails(1) {
+        return 1;
+    }
+
+    let mut lo = 1;
+    let mut hi = value;
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if fails(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    hi
+}
+
+fn replace_value(trace: &Trace, from: u64, to: u64) -> Trace {
+    let mut candidate = trace.clone();
+    for op in &mut candidate.ops {
+        if op_amount(op) == from {
+            set_op_amount(op, to);
+        }
+    }
+    for (_, balance) in &mut candidate.initial {
+        if *balance == from {
+            *balance = to;
+        }
+    }
+    candidate.initial.retain(|(_, balance)| *balance != 0);
+    candidate
+}
+
+fn with_initial_balance(trace: &Trace, index: usize, balance: u64) -> Trace {
+    let mut candidate = trace.clone();
+    if balance == 0 {
+        candidate.initial.remove(index);
+    } else {
+        candidate.initial[index].1 = balance;
+    }
+    candidate
+}
+
+fn op_amount(op: &Op) -> u64 {
+    match op {
+        Op::Deposit { amount, .. } | Op::Withdraw { amount, .. } | Op::Transfer { amount, .. } => {
+            *amount
+        }
+    }
+}
+
+fn set_op_amount(op: &mut Op, value: u64) {
+    match op {
+        Op::Deposit { amount, .. } | Op::Withdraw { amount, .. } | Op::Transfer { amount, .. } => {
+            *amount = value
+        }
+    }
+}
+
+fn rename_account(trace: &Trace, from: &str, to: &str) -> Trace {
+    let rename = |account: &String| {
+        if account == from {
+            to.to_string()
+        } else {
+            account.clone()
+        }
+    };
+
+    // merging two seeded accounts sums their balances rather than keeping both
+    let mut initial: Vec<(String, u64)> = Vec::with_capacity(trace.initial.len());
+    for (account, balance) in &trace.initial {
+        let account = rename(account);
+        match initial
+            .iter_mut()
+            .find(|(existing, _)| *existing == account)
+        {
+            Some((_, existing)) => *existing = existing.saturating_add(*balance),
+            None => initial.push((account, *balance)),
+        }
+    }
+
+    let ops = trace
+        .ops
+        .iter()
+        .map(|op| match op {
+            Op::Deposit { account, amount } => Op::Deposit {
+                account: rename(account),
+                amount: *amount,
+            },
+            Op::Withdraw { account, amount } => Op::Withdraw {
+                account: rename(account),
+                amount: *amount,
+            },
+            Op::Transfer { from, to, amount } => Op::Transfer {
+                from: rename(from),
+                to: rename(to),
+                amount: *amount,
+            },
+        })
+        .collect();
+
+    Trace { initial, ops }
+}
+
+// next name in a, b, ..., z, aa, ab, ... that is neither already handed out nor
+// still in use by another account that has not been renamed yet
+fn short_name(trace: &Trace, account: &str, renamed: &[String]) -> String {
+    let in_use = trace.accounts();
+    (0..)
+        .map(alphabetic_name)
+        .find(|name| !renamed.contains(name) && (name == account || !in_use.contains(name)))
+        .expect("unbounded name sequence")
+}
+
+fn alphabetic_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'a' + (index % 26) as u8);
+        index /= 26;
+        if index == 0 {
+            break;
+        }
+        index -= 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("ascii name")
+}
+
+// a candidate only counts if it fails with the same signature as the original,
+// otherwise shrinking tends to slip from one bug onto a shallower one
+fn diverges(trace: &Trace, failure: &FuzzFailure, sut: &mut dyn LedgerUnderTest) -> bool {
+    match fuzz::replay_trace(trace, failure.seed, failure.case_index, sut) {
+        Ok(()) => false,
+        Err(candidate) => Signature::of(&candidate) == Signature::of(failure),
+    }
+}
+
+// ddmin over complements: drop one of `granularity` chunks at a time, and
+// refine the chunks once none can be dropped. Ends 1-minimal.
+fn minimize_ops(ops: &[Op], mut diverges: impl FnMut(&[Op]) -> bool) -> Vec<Op> {
+    let mut current = ops.to_vec();
+    let mut granularity = 2;
+
+    while current.len() >= 2 {
+        let chunk = current.len().div_ceil(granularity);
+        let mut reduced = false;
+        let mut start = 0;
+
+        while start < current.len() {
+            let end = (start + chunk).min(current.len());
+            let candidate: Vec<Op> = current[..start]
+                .iter()
+                .chain(&current[end..])
+                .cloned()
+                .collect();
+            if diverges(&candidate) {
+                current = candidate;
+                granularity = (granularity - 1).max(2);
+                reduced = true;
+                break;
+            }
+            start = end;
+        }
+
+        if !reduced {
+            if granularity >= current.len() {
+                break;
+            }
+            granularity = (granularity * 2).min(current.len());
+        }
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Ledger;
+
+    fn deposits(amounts: impl IntoIterator<Item = u64>) -> Vec<Op> {
+        amounts
+            .into_iter()
+            .map(|amount| Op::Deposit {
+                account: "a".to_string(),
+                amount,
+            })
+            .collect()
+    }
+
+    fn amounts(ops: &[Op]) -> Vec<u64> {
+        ops.iter().map(op_amount).collect()
+    }
+
+    fn assert_one_minimal(ops: &[Op], fails: impl Fn(&[Op]) -> bool) {
+        assert!(fails(ops), "{:?} does not fail", amounts(ops));
+        for index in 0..ops.len() {
+            let mut candidate = ops.to_vec();
+            candidate.remove(index);
+            assert!(
+                !fails(&candidate),
+                "{:?} still fails without {}",
+                amounts(ops),
+                op_amount(&ops[index])
+            );
+        }
+    }
+
+    #[test]
+    fn minimize_ops_keeps_only_the_ops_the_failure_needs() {
+        let fails = |ops: &[Op]| {
+            let amounts = amounts(ops);
+            amounts.contains(&3) && amounts.contains(&11)
+        };
+        let minimized = minimize_ops(&deposits(0..20), fails);
+        assert_eq!(amounts(&minimized), [3, 11]);
+        assert_one_minimal(&minimized, fails);
+    }
+
+    #[test]
+    fn minimize_ops_ends_one_minimal_on_order_dependent_failures() {
+        // fails when some op is followed, anywhere later, by one with twice
+        // its amount; several minimal subsequences exist
+        let fails = |ops: &[Op]| {
+            let amounts = amounts(ops);
+            amounts.iter().enumerate().any(|(i, first)| {
+                *first != 0 && amounts[i + 1..].iter().any(|later| *later == first * 2)
+            })
+        };
+        let ops = deposits([5, 1, 7, 3, 9, 2, 6, 14, 4, 8]);
+        let minimized = minimize_ops(&ops, fails);
+        assert_eq!(minimized.len(), 2);
+        assert_one_minimal(&minimized, fails);
+    }
+
+    #[test]
+    fn minimize_ops_leaves_a_single_failing_op_alone() {
+        let ops = deposits([7]);
+        assert_eq!(minimize_ops(&ops, |ops| !ops.is_empty()), ops);
+    }
+
+    #[test]
+    fn minimize_value_finds_the_threshold_of_a_monotone_failure() {
+        assert_eq!(minimize_value(1000, |value| value >= 37), 37);
+        assert_eq!(
+            minimize_value(u64::MAX, |value| value > u64::MAX / 2),
+            u64::MAX / 2 + 1
+        );
+        assert_eq!(minimize_value(1000, |_| true), 0);
+        assert_eq!(minimize_value(1000, |value| value != 0), 1);
+    }
+
+    #[test]
+    fn minimize_value_returns_a_failing_value_when_failure_is_not_monotone() {
+        type Fails = fn(u64) -> bool;
+        let predicates: [(u64, Fails); 4] = [
+            (999, |value| value == 999 || (value % 5 == 0 && value > 400)),
+            (1000, |value| value % 2 == 0 && value >= 2),
+            (640, |value| (100..200).contains(&value) || value == 640),
+            (u64::MAX, |value| {
+                value == u64::MAX || value.count_ones() == 7
+            }),
+        ];
+        for (value, fails) in predicates {
+            let minimized = minimize_value(value, fails);
+            assert!(
+                fails(minimized),
+                "{} does not fail (from {})",
+                minimized,
+                value
+            );
+            assert!(minimized <= value);
+        }
+    }
+
+    // the only test here that drives the Lean model, since its runtime takes
+    // whichever thread initializes it first as its main thread
+    #[test]
+    fn fold_prefix_seeds_the_agreed_state_before_the_divergent_op() {
+        let withdraw_all = Op::Withdraw {
+            account: "a".to_string(),
+            amount: 7,
+        };
+        let mut ops = deposits([5]);
+        ops.push(Op::Deposit {
+            account: "b".to_string(),
+            amount: 3,
+        });
+        ops.push(withdraw_all.clone());
+        let trace = Trace {
+            initial: vec![("a".to_string(), 2)],
+            ops,
+        };
+        let mut ledger = Ledger::new();
+        let failure = fuzz::replay_trace(&trace, 0, 0, &mut ledger)
+            .expect_err("Ledger refuses to withdraw its whole balance");
+        assert_eq!(failure.step_index, 2);
+
+        let folded = fold_prefix(trace, &failure, &mut ledger);
+        assert_eq!(
+            folded,
+            Trace {
+                initial: vec![("a".to_string(), 7), ("b".to_string(), 3)],
+                ops: vec![withdraw_all],
+            }
+        );
+        // nothing left to fold once the divergent op comes first
+        assert_eq!(fold_prefix(folded.clone(), &failure, &mut ledger), folded);
+    }
+}
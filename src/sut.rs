@@ -0,0 +1,93 @@
+pub mod http;
+#[cfg(unix)]
+pub mod plugin;
+pub mod process;
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::ledger::Ledger;
+use crate::op::{ApplyError, Op};
+
+/// A ledger implementation the harness checks against the Lean model.
+///
+/// The harness builds one instance per worker thread and calls `reset`
+/// between cases, so implementations that are slow to start only start once
+/// per thread. Errors that are not an `ApplyError` (a lost connection, a
+/// malformed reply) should panic, from any method; the harness reports
+/// panics as the implementation's verdict for the step they happen in.
+pub trait LedgerUnderTest {
+    /// Shown in reports, e.g. the type or command under test.
+    fn name(&self) -> &str;
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError>;
+
+    /// Every account with a non-zero balance, sorted by account name.
+    fn snapshot(&mut self) -> Vec<(String, u64)>;
+
+    /// Back to an empty ledger.
+    fn reset(&mut self);
+}
+
+impl LedgerUnderTest for Ledger {
+    fn name(&self) -> &str {
+        "ledger::Ledger"
+    }
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        Ledger::apply(self, op)
+    }
+
+    fn snapshot(&mut self) -> Vec<(String, u64)> {
+        Ledger::snapshot(self)
+    }
+
+    fn reset(&mut self) {
+        *self = Ledger::new();
+    }
+}
+
+/// Builds the implementation under test. Defaults to `Ledger`.
+#[derive(Clone)]
+pub struct Implementation {
+    build: Arc<dyn Fn() -> Box<dyn LedgerUnderTest> + Send + Sync>,
+    replay_args: String,
+}
+
+impl Implementation {
+    pub fn new(build: impl Fn() -> Box<dyn LedgerUnderTest> + Send + Sync + 'static) -> Self {
+        Self {
+            build: Arc::new(build),
+            replay_args: String::new(),
+        }
+    }
+
+    /// The command-line flags that select this implementation, added to the
+    /// replay hints of its failures.
+    pub fn with_replay_args(mut self, args: impl Into<String>) -> Self {
+        self.replay_args = args.into();
+        self
+    }
+
+    pub fn replay_args(&self) -> &str {
+        &self.replay_args
+    }
+
+    pub fn build(&self) -> Box<dyn LedgerUnderTest> {
+        (self.build)()
+    }
+}
+
+impl Default for Implementation {
+    fn default() -> Self {
+        Self::new(|| Box::new(Ledger::new()))
+    }
+}
+
+impl fmt::Debug for Implementation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Implementation")
+            .field("replay_args", &self.replay_args)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,984 @@
+// Implementations behind an HTTP API. Each op becomes a request built from a
+// template, `METHOD PATH [BODY]`, whose `{account}`, `{from}`, `{to}`,
+// `{amount}` and `{session}` placeholders are filled in per request: percent
+// encoded in the path, as JSON values in the body. `{session}` is unique per
+// harness instance, so workers do not share a ledger. The default routes,
+// which `LoopbackServer` serves, are
+//
+//   POST /ledgers/{session}/deposit   {"account":{account},"amount":{amount}}
+//   POST /ledgers/{session}/withdraw  {"account":{account},"amount":{amount}}
+//   POST /ledgers/{session}/transfer  {"from":{from},"to":{to},"amount":{amount}}
+//   GET  /ledgers/{session}/balances
+//   POST /ledgers/{session}/reset
+//
+// An op's response body carries the verdict in the same fields as a
+// `--sut-process` reply, `{"ok":true}` or `{"ok":false,"error":...}`, under
+// any status below 500. When it also has `balances`, those are the snapshot;
+// otherwise the `balances` route is asked for `{"balances":[["alice",5]]}`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::json::Json;
+use crate::ledger::Ledger;
+use crate::op::{ApplyError, Op};
+use crate::report;
+use crate::sut::process::{decode_balances, decode_result};
+use crate::sut::LedgerUnderTest;
+
+/// One request: method, path below the base URL and an optional body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    method: String,
+    path: String,
+    body: Option<String>,
+}
+
+/// A value substituted for a placeholder.
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Text(&'a str),
+    Number(u64),
+}
+
+impl Value<'_> {
+    fn in_path(self) -> String {
+        match self {
+            Value::Text(text) => percent_encode(text),
+            Value::Number(number) => number.to_string(),
+        }
+    }
+
+    fn in_body(self) -> String {
+        match self {
+            Value::Text(text) => Json::string(text).to_string(),
+            Value::Number(number) => number.to_string(),
+        }
+    }
+}
+
+impl Template {
+    fn render(&self, values: &[(&str, Value)]) -> (String, Option<String>) {
+        let path = fill(&self.path, values, Value::in_path);
+        let body = self
+            .body
+            .as_deref()
+            .map(|body| fill(body, values, Value::in_body));
+        (path, body)
+    }
+}
+
+// replaces each `{name}` in `template` that has a value, in a single pass so
+// a value that itself contains a placeholder (an account named `{amount}`)
+// is left alone; other braces, such as a JSON body's, are kept
+fn fill<'a>(
+    template: &str,
+    values: &[(&str, Value<'a>)],
+    format: impl Fn(Value<'a>) -> String,
+) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let value = rest.find('}').and_then(|close| {
+            let name = &rest[1..close];
+            let (_, value) = values.iter().find(|(key, _)| *key == name)?;
+            Some((close, *value))
+        });
+        match value {
+            Some((close, value)) => {
+                filled.push_str(&format(value));
+                rest = &rest[close + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ' ');
+        let method = parts.next().unwrap_or_default();
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(format!("expected METHOD PATH [BODY], got {:?}", s));
+        }
+        let path = parts.next().unwrap_or_default();
+        if !path.starts_with('/') {
+            return Err(format!("path should start with /, got {:?}", path));
+        }
+        Ok(Template {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: parts.next().map(|body| body.trim().to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        if let Some(body) = &self.body {
+            write!(f, " {}", body)?;
+        }
+        Ok(())
+    }
+}
+
+/// The request template for each op kind, the snapshot and the reset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Templates {
+    pub deposit: Template,
+    pub withdraw: Template,
+    pub transfer: Template,
+    pub balances: Template,
+    pub reset: Template,
+}
+
+impl Templates {
+    pub const KEYS: [&'static str; 5] = ["deposit", "withdraw", "transfer", "balances", "reset"];
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Template> {
+        match key {
+            "deposit" => Some(&mut self.deposit),
+            "withdraw" => Some(&mut self.withdraw),
+            "transfer" => Some(&mut self.transfer),
+            "balances" => Some(&mut self.balances),
+            "reset" => Some(&mut self.reset),
+            _ => None,
+        }
+    }
+
+    /// `KEY=TEMPLATE` for every template that differs from the default.
+    pub fn overrides(&self) -> Vec<String> {
+        let default = Templates::default();
+        let pairs = [
+            ("deposit", &self.deposit, &default.deposit),
+            ("withdraw", &self.withdraw, &default.withdraw),
+            ("transfer", &self.transfer, &default.transfer),
+            ("balances", &self.balances, &default.balances),
+            ("reset", &self.reset, &default.reset),
+        ];
+        pairs
+            .into_iter()
+            .filter(|(_, template, default)| template != default)
+            .map(|(key, template, _)| format!("{}={}", key, template))
+            .collect()
+    }
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        let parse = |s: &str| s.parse::<Template>().expect("default templates parse");
+        Self {
+            deposit: parse(
+                r#"POST /ledgers/{session}/deposit {"account":{account},"amount":{amount}}"#,
+            ),
+            withdraw: parse(
+                r#"POST /ledgers/{session}/withdraw {"account":{account},"amount":{amount}}"#,
+            ),
+            transfer: parse(
+                r#"POST /ledgers/{session}/transfer {"from":{from},"to":{to},"amount":{amount}}"#,
+            ),
+            balances: parse("GET /ledgers/{session}/balances"),
+            reset: parse("POST /ledgers/{session}/reset"),
+        }
+    }
+}
+
+/// `http://host[:port][/prefix]`, with an IPv6 host in brackets; templates'
+/// paths are appended to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    host: String,
+    port: u16,
+    prefix: String,
+}
+
+impl FromStr for Url {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("expected an http:// URL, got {:?}", s))?;
+        let (authority, prefix) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        // an IPv6 host is bracketed, since its colons would read as a port
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, rest) = bracketed
+                    .split_once(']')
+                    .ok_or_else(|| format!("missing ] in {:?}", s))?;
+                match rest {
+                    "" => (host, None),
+                    _ => match rest.strip_prefix(':') {
+                        Some(port) => (host, Some(port)),
+                        None => return Err(format!("invalid port in {:?}", s)),
+                    },
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port
+                .parse::<u16>()
+                .map_err(|_| format!("invalid port in {:?}", s))?,
+            None => 80,
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in {:?}", s));
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            prefix: prefix.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+impl Url {
+    // `host:port` as the URL and the Host header write it
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}{}", self.authority(), self.prefix)
+    }
+}
+
+static SESSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// A ledger behind an HTTP API, spoken to over one keep-alive connection.
+///
+/// Transport errors, responses with status 500 or above and bodies that are
+/// not a verdict panic, which the harness reports as the implementation's
+/// verdict; the connection is reopened for the next request.
+pub struct HttpLedger {
+    url: Url,
+    templates: Templates,
+    session: String,
+    timeout: Duration,
+    name: String,
+    connection: Option<BufReader<TcpStream>>,
+    balances: Option<Vec<(String, u64)>>,
+}
+
+impl HttpLedger {
+    pub fn new(url: Url, templates: Templates, timeout: Duration) -> Self {
+        let session = format!(
+            "{}-{}",
+            std::process::id(),
+            SESSIONS.fetch_add(1, Ordering::Relaxed)
+        );
+        Self {
+            name: format!("http {}", url),
+            url,
+            templates,
+            session,
+            timeout,
+            connection: None,
+            balances: None,
+        }
+    }
+
+    /// Sends one reset, so an unreachable server shows up before a run
+    /// rather than as a panic in the middle of one.
+    pub fn check(&mut self) -> Result<(), String> {
+        let template = self.templates.reset.clone();
+        self.send(&template, &[], 300).map(|_| ())
+    }
+
+    /// Sends the request `template` renders to and returns the response's
+    /// body if its status is below `max_status`.
+    fn send(
+        &mut self,
+        template: &Template,
+        values: &[(&str, Value)],
+        max_status: u16,
+    ) -> Result<String, String> {
+        let session = self.session.clone();
+        let mut values = values.to_vec();
+        values.push(("session", Value::Text(&session)));
+        let (path, body) = template.render(&values);
+        let request = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            template.method,
+            self.url.prefix,
+            path,
+            self.url.authority(),
+            body.as_deref().map_or(0, str::len),
+            body.as_deref().unwrap_or_default()
+        );
+        let described = format!("{} {}", template.method, path);
+
+        // a kept-alive connection the server has since closed is replaced
+        // before sending. One that fails anyway is retried on a new one if the
+        // request never went out, or got no response and only reads; an op
+        // the server may already have applied is not sent twice
+        if self
+            .connection
+            .as_ref()
+            .is_some_and(|connection| !still_open(connection))
+        {
+            self.connection = None;
+        }
+        let reused = self.connection.is_some();
+        let response = match self.exchange(&request) {
+            Err(Exchange::NotSent(_)) if reused => self.exchange(&request),
+            Err(Exchange::NoResponse(_)) if reused && template.method == "GET" => {
+                self.exchange(&request)
+            }
+            response => response,
+        };
+        let (status, body) = response.map_err(|e| {
+            self.connection = None;
+            format!("{}: {}", described, e)
+        })?;
+        if status >= max_status {
+            return Err(format!("{}: status {}: {}", described, status, body));
+        }
+        Ok(body)
+    }
+
+    fn exchange(&mut self, request: &str) -> Result<(u16, String), Exchange> {
+        if self.connection.is_none() {
+            self.connection = Some(connect(&self.url, self.timeout).map_err(Exchange::Failed)?);
+        }
+        let connection = self.connection.as_mut().expect("just connected");
+        connection
+            .get_mut()
+            .write_all(request.as_bytes())
+            .map_err(Exchange::NotSent)?;
+        // interim 1xx responses can come ahead of the final one
+        let (message, status) = loop {
+            let message = match read_message(connection, false) {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    self.connection = None;
+                    return Err(Exchange::NoResponse(io::ErrorKind::UnexpectedEof.into()));
+                }
+                Err(e) => {
+                    self.connection = None;
+                    return Err(Exchange::Failed(e));
+                }
+            };
+            let status = status_code(&message.start).ok_or_else(|| {
+                Exchange::Failed(invalid(format!("bad status line {:?}", message.start)))
+            })?;
+            if status >= 200 {
+                break (message, status);
+            }
+        };
+        if message.close {
+            self.connection = None;
+        }
+        let body = String::from_utf8(message.body)
+            .map_err(|_| Exchange::Failed(invalid("response body is not UTF-8".to_string())))?;
+        Ok((status, body))
+    }
+
+    fn try_apply(&mut self, op: &Op) -> Result<Result<(), ApplyError>, String> {
+        let body = match op {
+            Op::Deposit { account, amount } => {
+                let template = self.templates.deposit.clone();
+                self.send(
+                    &template,
+                    &[
+                        ("account", Value::Text(account)),
+                        ("amount", Value::Number(*amount)),
+                    ],
+                    500,
+                )?
+            }
+            Op::Withdraw { account, amount } => {
+                let template = self.templates.withdraw.clone();
+                self.send(
+                    &template,
+                    &[
+                        ("account", Value::Text(account)),
+                        ("amount", Value::Number(*amount)),
+                    ],
+                    500,
+                )?
+            }
+            Op::Transfer { from, to, amount } => {
+                let template = self.templates.transfer.clone();
+                self.send(
+                    &template,
+                    &[
+                        ("from", Value::Text(from)),
+                        ("to", Value::Text(to)),
+                        ("amount", Value::Number(*amount)),
+                    ],
+                    500,
+                )?
+            }
+        };
+        let json = body
+            .parse::<Json>()
+            .and_then(|json| {
+                let result = decode_result(&json)?;
+                let balances = match json.get("balances") {
+                    Some(_) => Some(sorted(decode_balances(&json)?)),
+                    None => None,
+                };
+                Ok((result, balances))
+            })
+            .map_err(|e| format!("malformed response {:?}: {}", body, e))?;
+        self.balances = json.1;
+        Ok(json.0)
+    }
+
+    fn try_snapshot(&mut self) -> Result<Vec<(String, u64)>, String> {
+        let template = self.templates.balances.clone();
+        let body = self.send(&template, &[], 300)?;
+        body.parse::<Json>()
+            .and_then(|json| decode_balances(&json))
+            .map(sorted)
+            .map_err(|e| format!("malformed balances {:?}: {}", body, e))
+    }
+}
+
+// snapshots are sorted by account, whatever order the server lists them in
+fn sorted(mut balances: Vec<(String, u64)>) -> Vec<(String, u64)> {
+    balances.sort_by(|a, b| a.0.cmp(&b.0));
+    balances
+}
+
+enum Exchange {
+    /// The request could not be written, so the server never saw it.
+    NotSent(io::Error),
+    /// The request went out but the connection closed before any of a
+    /// response arrived; the server may have acted on it.
+    NoResponse(io::Error),
+    Failed(io::Error),
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exchange::NotSent(e) | Exchange::NoResponse(e) | Exchange::Failed(e) => {
+                write!(f, "{}", e)
+            }
+        }
+    }
+}
+
+// whether a kept-alive connection is still open: with nothing buffered, a
+// read that would block means the server has not closed it while idle
+fn still_open(connection: &BufReader<TcpStream>) -> bool {
+    if !connection.buffer().is_empty() {
+        return false;
+    }
+    let stream = connection.get_ref();
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let open = matches!(stream.peek(&mut [0]), Err(e) if e.kind() == io::ErrorKind::WouldBlock);
+    stream.set_nonblocking(false).is_ok() && open
+}
+
+impl LedgerUnderTest for HttpLedger {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        match self.try_apply(op) {
+            Ok(result) => result,
+            Err(message) => panic!("{} on {}: {}", self.name, op, message),
+        }
+    }
+
+    fn snapshot(&mut self) -> Vec<(String, u64)> {
+        if let Some(balances) = &self.balances {
+            return balances.clone();
+        }
+        match self.try_snapshot() {
+            Ok(balances) => balances,
+            Err(message) => panic!("{} on snapshot: {}", self.name, message),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.balances = Some(Vec::new());
+        if let Err(message) = self.check() {
+            panic!("{} failed to reset: {}", self.name, message);
+        }
+    }
+}
+
+fn connect(url: &Url, timeout: Duration) -> io::Result<BufReader<TcpStream>> {
+    let mut last = io::Error::new(io::ErrorKind::NotFound, "host has no addresses");
+    for addr in (url.host.as_str(), url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                stream.set_nodelay(true)?;
+                return Ok(BufReader::new(stream));
+            }
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// A request or response: its first line, body, and whether the connection
+/// closes after it.
+struct Message {
+    start: String,
+    body: Vec<u8>,
+    close: bool,
+}
+
+fn status_code(status_line: &str) -> Option<u16> {
+    status_line.split(' ').nth(1)?.parse::<u16>().ok()
+}
+
+/// Reads one message, or `None` at a clean end of the connection. A request
+/// without a length has no body; a response without one runs to the end of
+/// the connection, except that 1xx, 204 and 304 responses never have one.
+fn read_message(reader: &mut impl BufRead, request: bool) -> io::Result<Option<Message>> {
+    let mut start = String::new();
+    if reader.read_line(&mut start)? == 0 {
+        return Ok(None);
+    }
+    let start = start.trim_end().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("bad header {:?}", line)))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let mut close = header("connection").is_some_and(|value| value.eq_ignore_ascii_case("close"))
+        || start.ends_with("HTTP/1.0")
+        || start.starts_with("HTTP/1.0");
+    let bodiless = !request
+        && status_code(&start).is_some_and(|status| status < 200 || status == 204 || status == 304);
+    if bodiless {
+        return Ok(Some(Message {
+            start,
+            body: Vec::new(),
+            close,
+        }));
+    }
+    let mut body = Vec::new();
+    if header("transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+        loop {
+            let mut size = String::new();
+            if reader.read_line(&mut size)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let size = size.trim_end().split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|_| invalid(format!("bad chunk size {:?}", size)))?;
+            if size == 0 {
+                // the last chunk, then any trailers up to a blank line
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                break;
+            }
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk)?;
+            body.extend_from_slice(&chunk[..size]);
+        }
+    } else if let Some(length) = header("content-length") {
+        let length = length
+            .parse::<usize>()
+            .map_err(|_| invalid(format!("bad content-length {:?}", length)))?;
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else if !request {
+        reader.read_to_end(&mut body)?;
+        close = true;
+    }
+    Ok(Some(Message { start, body, close }))
+}
+
+/// Serves `Ledger` on the default routes, one ledger per session, so the
+/// HTTP adapter can be checked end to end without a real service.
+pub struct LoopbackServer {
+    addr: SocketAddr,
+}
+
+impl LoopbackServer {
+    /// Binds `addr` and serves it on a background thread for the rest of
+    /// the process. Port 0 picks a free port.
+    pub fn start(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        thread::spawn(move || serve(listener));
+        Ok(Self { addr })
+    }
+
+    pub fn url(&self) -> Url {
+        Url {
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            prefix: String::new(),
+        }
+    }
+}
+
+/// Serves `Ledger` on `listener` until the process exits, a thread per
+/// connection.
+pub fn serve(listener: TcpListener) {
+    let ledgers = Arc::new(Mutex::new(HashMap::new()));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let ledgers = Arc::clone(&ledgers);
+        thread::spawn(move || {
+            let _ = handle_connection(stream, &ledgers);
+        });
+    }
+}
+
+type Ledgers = Mutex<HashMap<String, Ledger>>;
+
+fn handle_connection(stream: TcpStream, ledgers: &Ledgers) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_message(&mut reader, true)? {
+        let (status, body) = route(&request, ledgers);
+        let reason = match status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            _ => "Conflict",
+        };
+        let body = body.to_string();
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{}",
+            status,
+            reason,
+            body.len(),
+            if request.close {
+                "Connection: close\r\n"
+            } else {
+                ""
+            },
+            body
+        )?;
+        writer.flush()?;
+        if request.close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn route(request: &Message, ledgers: &Ledgers) -> (u16, Json) {
+    let error =
+        |status, message: String| (status, Json::object([("error", Json::string(message))]));
+    let mut parts = request.start.split(' ');
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let segments: Vec<&str> = target.trim_start_matches('/').split('/').collect();
+    let (session, action) = match segments.as_slice() {
+        ["ledgers", session, action] => match percent_decode(session) {
+            Some(session) => (session, *action),
+            None => return error(400, format!("bad session {:?}", session)),
+        },
+        _ => return error(404, format!("no route for {} {}", method, target)),
+    };
+    let mut ledgers = ledgers.lock().unwrap_or_else(|e| e.into_inner());
+    let ledger = ledgers.entry(session).or_default();
+    match (method, action) {
+        ("GET", "balances") => (
+            200,
+            Json::object([("balances", report::balances_json(&ledger.snapshot()))]),
+        ),
+        ("POST", "reset") => {
+            *ledger = Ledger::new();
+            (200, report::result_json(&Ok(())))
+        }
+        ("POST", "deposit" | "withdraw" | "transfer") => {
+            let op = std::str::from_utf8(&request.body)
+                .map_err(|_| "body is not UTF-8".to_string())
+                .and_then(|body| body.parse::<Json>())
+                .and_then(|json| op_from_body(action, &json));
+            match op {
+                Ok(op) => {
+                    let result = ledger.apply(&op);
+                    let status = if result.is_ok() { 200 } else { 409 };
+                    (status, report::result_json(&result))
+                }
+                Err(message) => error(400, message),
+            }
+        }
+        _ => error(404, format!("no route for {} {}", method, target)),
+    }
+}
+
+fn op_from_body(kind: &str, json: &Json) -> Result<Op, String> {
+    let string = |key: &str| {
+        json.get(key)
+            .and_then(Json::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("`{}` should be a string", key))
+    };
+    let amount = json
+        .get("amount")
+        .and_then(Json::as_u64)
+        .ok_or_else(|| "`amount` should be a u64".to_string())?;
+    Ok(match kind {
+        "deposit" => Op::Deposit {
+            account: string("account")?,
+            amount,
+        },
+        "withdraw" => Op::Withdraw {
+            account: string("account")?,
+            amount,
+        },
+        _ => Op::Transfer {
+            from: string("from")?,
+            to: string("to")?,
+            amount,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_leaves_placeholders_inside_values_alone() {
+        let templates = Templates::default();
+        let values = [
+            ("from", Value::Text("{to}")),
+            ("to", Value::Text("{amount}{session}")),
+            ("amount", Value::Number(7)),
+            ("session", Value::Text("s/{from}")),
+        ];
+        let (path, body) = templates.transfer.render(&values);
+        assert_eq!(path, "/ledgers/s%2F%7Bfrom%7D/transfer");
+        assert_eq!(
+            body.as_deref(),
+            Some(r#"{"from":"{to}","to":"{amount}{session}","amount":7}"#)
+        );
+
+        let template: Template = "PUT /a/{unknown}/{amount}} {{amount}:{{}}"
+            .parse()
+            .expect("template parses");
+        let (path, body) = template.render(&[("amount", Value::Number(1))]);
+        assert_eq!(path, "/a/{unknown}/1}");
+        assert_eq!(body.as_deref(), Some("{1:{{}}"));
+    }
+
+    fn read_all(bytes: &str, request: bool) -> Vec<(String, String, bool)> {
+        let mut reader = io::Cursor::new(bytes.as_bytes());
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut reader, request).expect("well-formed") {
+            let body = String::from_utf8(message.body).expect("UTF-8 body");
+            messages.push((message.start, body, message.close));
+        }
+        messages
+    }
+
+    fn message(start: &str, body: &str, close: bool) -> (String, String, bool) {
+        (start.to_string(), body.to_string(), close)
+    }
+
+    #[test]
+    fn bodiless_responses_end_at_their_headers() {
+        let responses = concat!(
+            "HTTP/1.1 100 Continue\r\n\r\n",
+            "HTTP/1.1 204 No Content\r\n\r\n",
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: 12\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n",
+        );
+        assert_eq!(
+            read_all(responses, false),
+            [
+                message("HTTP/1.1 100 Continue", "", false),
+                message("HTTP/1.1 204 No Content", "", false),
+                message("HTTP/1.1 304 Not Modified", "", false),
+                message("HTTP/1.1 200 OK", "ok", false),
+                message("HTTP/1.1 204 No Content", "", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunked_and_keep_alive_messages_follow_each_other() {
+        let responses = concat!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "3\r\n{\"o\r\n",
+            "a;name=value\r\nk\":true}\r\n\r\n",
+            "0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked\r\n\r\n",
+            "2\r\nhi\r\n0\r\nExpires: never\r\nX-Note: trailer\r\n\r\n",
+            "HTTP/1.1 409 Conflict\r\nContent-Length: 4\r\n\r\nnope",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\n\r\nto the end\r\nof the connection",
+        );
+        assert_eq!(
+            read_all(responses, false),
+            [
+                message("HTTP/1.1 200 OK", "{\"ok\":true}\r\n", false),
+                message("HTTP/1.1 200 OK", "hi", false),
+                message("HTTP/1.1 409 Conflict", "nope", false),
+                message("HTTP/1.1 200 OK", "", false),
+                message("HTTP/1.1 200 OK", "to the end\r\nof the connection", true),
+            ]
+        );
+
+        let requests = concat!(
+            "POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+            "GET /b HTTP/1.1\r\n\r\n",
+            "POST /c HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nx\r\n0\r\n\r\n",
+            "GET /d HTTP/1.0\r\n\r\n",
+            "GET /e HTTP/1.1\r\nConnection: Close\r\n\r\n",
+        );
+        assert_eq!(
+            read_all(requests, true),
+            [
+                message("POST /a HTTP/1.1", "{}", false),
+                message("GET /b HTTP/1.1", "", false),
+                message("POST /c HTTP/1.1", "x", false),
+                message("GET /d HTTP/1.0", "", true),
+                message("GET /e HTTP/1.1", "", true),
+            ]
+        );
+        assert_eq!(
+            read_all("HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok", false),
+            [message("HTTP/1.0 200 OK", "ok", true)]
+        );
+
+        for truncated in [
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nok",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length",
+        ] {
+            let mut reader = io::Cursor::new(truncated.as_bytes());
+            assert!(
+                read_message(&mut reader, false).is_err(),
+                "{:?} read",
+                truncated
+            );
+        }
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        for text in [
+            "",
+            "alice",
+            "a-b.c_d~e",
+            "\0",
+            "%",
+            "%41",
+            "a/b",
+            "{session}",
+            "b o b",
+            "say \"hi\"",
+            "zoë 🦀",
+        ] {
+            let encoded = percent_encode(text);
+            assert!(
+                encoded
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || b"-._~%".contains(&byte)),
+                "{:?} encodes to {:?}",
+                text,
+                encoded
+            );
+            assert_eq!(percent_decode(&encoded).as_deref(), Some(text));
+        }
+        assert_eq!(percent_encode("a/b c"), "a%2Fb%20c");
+        assert_eq!(percent_decode("a%2fb+c").as_deref(), Some("a/b+c"));
+        for malformed in ["%", "%4", "%zz", "%FF", "a%2"] {
+            assert_eq!(percent_decode(malformed), None, "{:?} decoded", malformed);
+        }
+    }
+}
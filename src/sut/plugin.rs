@@ -0,0 +1,292 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::op::{ApplyError, Op};
+use crate::sut::LedgerUnderTest;
+
+// glibc before 2.34 keeps these in libdl rather than libc
+#[cfg_attr(target_os = "linux", link(name = "dl"))]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *mut c_char;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+const RTLD_NOW: c_int = 2;
+
+// ledger_sut_error in ledger_sut.h
+#[repr(C)]
+struct RawError {
+    tag: u8,
+    account: *const c_char,
+    account_len: usize,
+    balance: u64,
+    amount: u64,
+}
+
+const ERROR_NONE: u8 = 0;
+const ERROR_INSUFFICIENT_FUNDS: u8 = 1;
+const ERROR_OVERFLOW: u8 = 2;
+
+type EntryFn =
+    extern "C" fn(ctx: *mut c_void, account: *const c_char, account_len: usize, balance: u64);
+
+type NewFn = unsafe extern "C" fn() -> *mut c_void;
+type FreeFn = unsafe extern "C" fn(ledger: *mut c_void);
+type ApplyOneFn = unsafe extern "C" fn(
+    ledger: *mut c_void,
+    account: *const c_char,
+    account_len: usize,
+    amount: u64,
+    error: *mut RawError,
+);
+type ApplyTransferFn = unsafe extern "C" fn(
+    ledger: *mut c_void,
+    from_account: *const c_char,
+    from_len: usize,
+    to_account: *const c_char,
+    to_len: usize,
+    amount: u64,
+    error: *mut RawError,
+);
+type BalanceFn =
+    unsafe extern "C" fn(ledger: *mut c_void, account: *const c_char, account_len: usize) -> u64;
+type ListFn = unsafe extern "C" fn(ledger: *mut c_void, entry_fn: EntryFn, ctx: *mut c_void);
+
+/// A loaded plugin library and the functions of `ledger_sut.h` it exports.
+/// The library stays loaded until the last `PluginLedger` using it is gone.
+pub struct Plugin {
+    path: PathBuf,
+    handle: *mut c_void,
+    new: NewFn,
+    free: FreeFn,
+    apply_deposit: ApplyOneFn,
+    apply_withdraw: ApplyOneFn,
+    apply_transfer: ApplyTransferFn,
+    balance: BalanceFn,
+    list: ListFn,
+}
+
+// the handle and function pointers are only read; per-ledger state lives
+// behind the pointers `new` returns, which stay on their thread
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    /// Loads the library at `path` and resolves every function, so a missing
+    /// symbol shows up before a run rather than in the middle of one.
+    pub fn load(path: &Path) -> Result<Arc<Self>, String> {
+        // dlopen searches the library path for a bare file name
+        let path = if path.components().count() == 1 {
+            Path::new(".").join(path)
+        } else {
+            path.to_path_buf()
+        };
+        let filename = CString::new(path.as_os_str().as_encoded_bytes())
+            .map_err(|_| format!("path contains NUL: {}", path.display()))?;
+        let handle = unsafe { dlopen(filename.as_ptr(), RTLD_NOW) };
+        if handle.is_null() {
+            return Err(last_dl_error());
+        }
+        let symbol = |name: &str| -> Result<*mut c_void, String> {
+            let symbol_name = CString::new(name).expect("symbol names have no NUL");
+            let symbol = unsafe { dlsym(handle, symbol_name.as_ptr()) };
+            if symbol.is_null() {
+                Err(format!("missing symbol {}", name))
+            } else {
+                Ok(symbol)
+            }
+        };
+        let resolve = || unsafe {
+            use std::mem::transmute;
+            Ok::<_, String>(Plugin {
+                path: path.clone(),
+                handle,
+                new: transmute::<*mut c_void, NewFn>(symbol("ledger_sut_new")?),
+                free: transmute::<*mut c_void, FreeFn>(symbol("ledger_sut_free")?),
+                apply_deposit: transmute::<*mut c_void, ApplyOneFn>(symbol(
+                    "ledger_sut_apply_deposit",
+                )?),
+                apply_withdraw: transmute::<*mut c_void, ApplyOneFn>(symbol(
+                    "ledger_sut_apply_withdraw",
+                )?),
+                apply_transfer: transmute::<*mut c_void, ApplyTransferFn>(symbol(
+                    "ledger_sut_apply_transfer",
+                )?),
+                balance: transmute::<*mut c_void, BalanceFn>(symbol("ledger_sut_balance")?),
+                list: transmute::<*mut c_void, ListFn>(symbol("ledger_sut_list")?),
+            })
+        };
+        match resolve() {
+            Ok(plugin) => Ok(Arc::new(plugin)),
+            Err(message) => {
+                unsafe { dlclose(handle) };
+                Err(message)
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        unsafe { dlclose(self.handle) };
+    }
+}
+
+fn last_dl_error() -> String {
+    let message = unsafe { dlerror() };
+    if message.is_null() {
+        "dlopen failed".to_string()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+extern "C" fn collect_entry(
+    ctx: *mut c_void,
+    account: *const c_char,
+    account_len: usize,
+    balance: u64,
+) {
+    let entries = unsafe { &mut *(ctx as *mut Vec<(String, u64)>) };
+    entries.push((unsafe { account_string(account, account_len) }, balance));
+}
+
+unsafe fn account_string(account: *const c_char, account_len: usize) -> String {
+    if account_len == 0 {
+        return String::new();
+    }
+    let bytes = std::slice::from_raw_parts(account as *const u8, account_len);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// One ledger created by a plugin's `ledger_sut_new`.
+///
+/// Besides comparing `ledger_sut_list` against the model, `snapshot` checks
+/// that `ledger_sut_balance` agrees with it for every listed account.
+pub struct PluginLedger {
+    plugin: Arc<Plugin>,
+    ledger: *mut c_void,
+    name: String,
+}
+
+impl PluginLedger {
+    pub fn new(plugin: Arc<Plugin>) -> Self {
+        let ledger = unsafe { (plugin.new)() };
+        assert!(!ledger.is_null(), "ledger_sut_new returned NULL");
+        Self {
+            name: format!("plugin {}", plugin.path.display()),
+            plugin,
+            ledger,
+        }
+    }
+}
+
+impl LedgerUnderTest for PluginLedger {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        let mut error = RawError {
+            tag: ERROR_NONE,
+            account: std::ptr::null(),
+            account_len: 0,
+            balance: 0,
+            amount: 0,
+        };
+        unsafe {
+            match op {
+                Op::Deposit { account, amount } => (self.plugin.apply_deposit)(
+                    self.ledger,
+                    account.as_ptr() as *const c_char,
+                    account.len(),
+                    *amount,
+                    &mut error,
+                ),
+                Op::Withdraw { account, amount } => (self.plugin.apply_withdraw)(
+                    self.ledger,
+                    account.as_ptr() as *const c_char,
+                    account.len(),
+                    *amount,
+                    &mut error,
+                ),
+                Op::Transfer { from, to, amount } => (self.plugin.apply_transfer)(
+                    self.ledger,
+                    from.as_ptr() as *const c_char,
+                    from.len(),
+                    to.as_ptr() as *const c_char,
+                    to.len(),
+                    *amount,
+                    &mut error,
+                ),
+            }
+        }
+        let account = || unsafe { account_string(error.account, error.account_len) };
+        match error.tag {
+            ERROR_NONE => Ok(()),
+            ERROR_INSUFFICIENT_FUNDS => Err(ApplyError::InsufficientFunds {
+                account: account(),
+                balance: error.balance,
+                amount: error.amount,
+            }),
+            ERROR_OVERFLOW => Err(ApplyError::Overflow {
+                account: account(),
+                balance: error.balance,
+                amount: error.amount,
+            }),
+            tag => panic!("{} on {}: unknown error tag {}", self.name, op, tag),
+        }
+    }
+
+    fn snapshot(&mut self) -> Vec<(String, u64)> {
+        let mut items: Vec<(String, u64)> = Vec::new();
+        unsafe {
+            (self.plugin.list)(
+                self.ledger,
+                collect_entry,
+                &mut items as *mut Vec<(String, u64)> as *mut c_void,
+            );
+        }
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        for (account, listed) in &items {
+            let balance = unsafe {
+                (self.plugin.balance)(
+                    self.ledger,
+                    account.as_ptr() as *const c_char,
+                    account.len(),
+                )
+            };
+            if balance != *listed {
+                panic!(
+                    "{}: ledger_sut_list has {:?} at {} but ledger_sut_balance says {}",
+                    self.name, account, listed, balance
+                );
+            }
+        }
+        items
+    }
+
+    fn reset(&mut self) {
+        unsafe {
+            (self.plugin.free)(self.ledger);
+            self.ledger = (self.plugin.new)();
+        }
+        assert!(!self.ledger.is_null(), "ledger_sut_new returned NULL");
+    }
+}
+
+impl Drop for PluginLedger {
+    fn drop(&mut self) {
+        unsafe { (self.plugin.free)(self.ledger) };
+    }
+}
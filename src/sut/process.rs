@@ -0,0 +1,308 @@
+// Out-of-process implementations, one JSON object per line each way. The
+// harness writes one of
+//
+//   {"kind":"deposit","account":"alice","amount":5}
+//   {"kind":"withdraw","account":"alice","amount":5}
+//   {"kind":"transfer","from":"alice","to":"bob","amount":5}
+//   {"kind":"reset"}
+//
+// to the process's stdin and reads back its verdict and every non-zero
+// balance, in any order, from stdout:
+//
+//   {"ok":true,"balances":[["alice",5]]}
+//   {"ok":false,"error":"insufficient-funds","account":"alice","balance":0,"amount":5,"balances":[]}
+//
+// `error` is `insufficient-funds` or `overflow`; a reset answers like a
+// successful op. stderr is passed through.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::json::Json;
+use crate::op::{ApplyError, Op};
+use crate::report;
+use crate::sut::LedgerUnderTest;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    Apply(Op),
+    Reset,
+}
+
+/// The implementation's answer to a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reply {
+    pub result: Result<(), ApplyError>,
+    pub balances: Vec<(String, u64)>,
+}
+
+pub fn encode_request(request: &Request) -> String {
+    match request {
+        Request::Apply(op) => report::op_json(op).to_string(),
+        Request::Reset => Json::object([("kind", Json::string("reset"))]).to_string(),
+    }
+}
+
+pub fn decode_request(line: &str) -> Result<Request, String> {
+    let json = line.parse::<Json>()?;
+    let field = |key: &str| json.get(key).ok_or_else(|| format!("missing `{}`", key));
+    let string = |key: &str| {
+        field(key)?
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("`{}` should be a string", key))
+    };
+    let amount = || {
+        field("amount")?
+            .as_u64()
+            .ok_or_else(|| "`amount` should be a u64".to_string())
+    };
+    let op = match string("kind")?.as_str() {
+        "reset" => return Ok(Request::Reset),
+        "deposit" => Op::Deposit {
+            account: string("account")?,
+            amount: amount()?,
+        },
+        "withdraw" => Op::Withdraw {
+            account: string("account")?,
+            amount: amount()?,
+        },
+        "transfer" => Op::Transfer {
+            from: string("from")?,
+            to: string("to")?,
+            amount: amount()?,
+        },
+        other => return Err(format!("unknown kind: {}", other)),
+    };
+    Ok(Request::Apply(op))
+}
+
+pub fn encode_reply(reply: &Reply) -> String {
+    let mut json = report::result_json(&reply.result);
+    if let Json::Object(fields) = &mut json {
+        fields.push((
+            "balances".to_string(),
+            report::balances_json(&reply.balances),
+        ));
+    }
+    json.to_string()
+}
+
+pub fn decode_reply(line: &str) -> Result<Reply, String> {
+    let json = line.parse::<Json>()?;
+    Ok(Reply {
+        result: decode_result(&json)?,
+        balances: decode_balances(&json)?,
+    })
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, String> {
+    json.get(key).ok_or_else(|| format!("missing `{}`", key))
+}
+
+/// The verdict in a reply: `ok`, and the error fields when it is false.
+pub(crate) fn decode_result(json: &Json) -> Result<Result<(), ApplyError>, String> {
+    let ok = field(json, "ok")?
+        .as_bool()
+        .ok_or_else(|| "`ok` should be a bool".to_string())?;
+    if ok {
+        return Ok(Ok(()));
+    }
+    let account = field(json, "account")?
+        .as_str()
+        .ok_or_else(|| "`account` should be a string".to_string())?
+        .to_string();
+    let number = |key: &str| {
+        field(json, key)?
+            .as_u64()
+            .ok_or_else(|| format!("`{}` should be a u64", key))
+    };
+    let balance = number("balance")?;
+    let amount = number("amount")?;
+    match field(json, "error")?.as_str() {
+        Some("insufficient-funds") => Ok(Err(ApplyError::InsufficientFunds {
+            account,
+            balance,
+            amount,
+        })),
+        Some("overflow") => Ok(Err(ApplyError::Overflow {
+            account,
+            balance,
+            amount,
+        })),
+        _ => Err("`error` should be insufficient-funds or overflow".to_string()),
+    }
+}
+
+/// The `balances` of a reply, as `[account, balance]` pairs.
+pub(crate) fn decode_balances(json: &Json) -> Result<Vec<(String, u64)>, String> {
+    field(json, "balances")?
+        .as_array()
+        .ok_or_else(|| "`balances` should be an array".to_string())?
+        .iter()
+        .map(|entry| match entry.as_array() {
+            Some([account, balance]) => account
+                .as_str()
+                .zip(balance.as_u64())
+                .map(|(account, balance)| (account.to_string(), balance)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| "`balances` should be [account, balance] pairs".to_string())
+}
+
+/// A ledger in another process, spoken to over stdin and stdout.
+///
+/// The process is started on first use and restarted by `reset` after it
+/// crashed, hung past `timeout` or sent something that is not a reply; the
+/// op that ran into it panics with what went wrong, which the harness
+/// reports as the implementation's verdict.
+pub struct ProcessLedger {
+    command: Vec<String>,
+    name: String,
+    timeout: Duration,
+    running: Option<Running>,
+    balances: Vec<(String, u64)>,
+}
+
+struct Running {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<std::io::Result<String>>,
+}
+
+impl ProcessLedger {
+    /// `command` is the program followed by its arguments.
+    pub fn new(command: Vec<String>, timeout: Duration) -> Self {
+        Self {
+            name: format!("process `{}`", command.join(" ")),
+            command,
+            timeout,
+            running: None,
+            balances: Vec::new(),
+        }
+    }
+
+    /// Starts the process and has it reset once, so a bad command shows up
+    /// before a run rather than as a panic in the middle of one.
+    pub fn check(&mut self) -> Result<(), String> {
+        match self.request(&Request::Reset)?.result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(format!("reset failed: {}", error)),
+        }
+    }
+
+    fn start(&mut self) -> Result<&mut Running, String> {
+        if self.running.is_none() {
+            let (program, args) = self
+                .command
+                .split_first()
+                .ok_or_else(|| "empty command".to_string())?;
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::inherit())
+                .spawn()
+                .map_err(|e| format!("failed to start: {}", e))?;
+            let stdin = child.stdin.take().expect("piped stdin");
+            let stdout = child.stdout.take().expect("piped stdout");
+            // a blocking read cannot time out, so lines come over a channel
+            let (sender, replies) = mpsc::channel();
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+            self.running = Some(Running {
+                child,
+                stdin,
+                replies,
+            });
+        }
+        Ok(self.running.as_mut().expect("just started"))
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Reply, String> {
+        let timeout = self.timeout;
+        let line = encode_request(request);
+        let running = self.start()?;
+        let reply = writeln!(running.stdin, "{}", line)
+            .and_then(|()| running.stdin.flush())
+            .map_err(|e| format!("failed to send request: {}", e))
+            .and_then(|()| match running.replies.recv_timeout(timeout) {
+                Ok(Ok(line)) => {
+                    decode_reply(&line).map_err(|e| format!("malformed reply {:?}: {}", line, e))
+                }
+                Ok(Err(e)) => Err(format!("failed to read reply: {}", e)),
+                Err(RecvTimeoutError::Timeout) => Err(format!("no reply within {:?}", timeout)),
+                Err(RecvTimeoutError::Disconnected) => Err(match running.child.wait() {
+                    Ok(status) => format!("exited ({})", status),
+                    Err(e) => format!("exited: {}", e),
+                }),
+            });
+        if reply.is_err() {
+            self.stop();
+        }
+        reply
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut running) = self.running.take() {
+            let _ = running.child.kill();
+            let _ = running.child.wait();
+        }
+    }
+}
+
+impl LedgerUnderTest for ProcessLedger {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        match self.request(&Request::Apply(op.clone())) {
+            Ok(mut reply) => {
+                // snapshots are sorted by account, whatever order the process
+                // keeps its balances in
+                reply.balances.sort_by(|a, b| a.0.cmp(&b.0));
+                self.balances = reply.balances;
+                reply.result
+            }
+            Err(message) => panic!("{} on {}: {}", self.name, op, message),
+        }
+    }
+
+    fn snapshot(&mut self) -> Vec<(String, u64)> {
+        self.balances.clone()
+    }
+
+    fn reset(&mut self) {
+        // a process that answers a reset with an error or balances is
+        // restarted rather than trusted
+        let fresh = match self.request(&Request::Reset) {
+            Ok(reply) => reply.result.is_ok() && reply.balances.is_empty(),
+            Err(_) => false,
+        };
+        if !fresh {
+            self.stop();
+            if let Err(message) = self.request(&Request::Reset) {
+                panic!("{} failed to reset: {}", self.name, message);
+            }
+        }
+        self.balances.clear();
+    }
+}
+
+impl Drop for ProcessLedger {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,127 @@
+// Versioned, line-based trace files:
+//
+//   # any comment
+//   version 1
+//   initial(alice, 16)
+//   initial("b o b", 3)
+//   deposit(alice, 5)
+//   transfer(alice, "b o b", 2)
+//
+// `initial` lines seed balances before the ops run; op lines use the same
+// syntax as `Op`'s Display. Blank lines and `#` comments are ignored.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::fuzz::{FuzzFailure, Trace};
+use crate::op::{self, AccountName, Op};
+
+pub const VERSION: u32 = 1;
+
+pub fn format(trace: &Trace, comments: &[String]) -> String {
+    let mut out = String::new();
+    for comment in comments {
+        out.push_str(&format!("# {}\n", comment));
+    }
+    out.push_str(&format!("version {}\n", VERSION));
+    for (account, balance) in &trace.initial {
+        out.push_str(&format!("initial({}, {})\n", AccountName(account), balance));
+    }
+    for op in &trace.ops {
+        out.push_str(&format!("{}\n", op));
+    }
+    out
+}
+
+pub fn parse(text: &str) -> Result<Trace, String> {
+    let mut version = None;
+    let mut trace = Trace {
+        initial: Vec::new(),
+        ops: Vec::new(),
+    };
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |message: String| format!("line {}: {}", index + 1, message);
+
+        if version.is_none() {
+            let value = line
+                .strip_prefix("version ")
+                .ok_or_else(|| at_line("expected `version N` before any entries".to_string()))?;
+            let value = value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| at_line(format!("invalid version: {}", value.trim())))?;
+            if value != VERSION {
+                return Err(at_line(format!(
+                    "unsupported trace version {} (expected {})",
+                    value, VERSION
+                )));
+            }
+            version = Some(value);
+            continue;
+        }
+
+        if line.starts_with("initial(") {
+            if !trace.ops.is_empty() {
+                return Err(at_line("initial balances must come before ops".to_string()));
+            }
+            let (_, args) = op::parse_call(line).map_err(at_line)?;
+            let [account, balance] = args.as_slice() else {
+                return Err(at_line(format!(
+                    "expected initial(account, balance): {}",
+                    line
+                )));
+            };
+            let balance = op::parse_amount(balance).map_err(at_line)?;
+            trace.initial.push((account.clone(), balance));
+        } else {
+            trace.ops.push(line.parse::<Op>().map_err(at_line)?);
+        }
+    }
+
+    if version.is_none() {
+        return Err("missing `version N` line".to_string());
+    }
+    Ok(trace)
+}
+
+pub fn read(path: &Path) -> Result<Trace, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes the full history of `failure`, and its minimized trace if there is
+/// one, into `dir`. Returns the written paths.
+pub fn write_failure(dir: &Path, failure: &FuzzFailure) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let stem = format!("seed-{}-case-{}", failure.seed, failure.case_index);
+    let comments = vec![
+        format!(
+            "seed={} case={} step={}",
+            failure.seed, failure.case_index, failure.step_index
+        ),
+        format!("diverging op: {}", failure.op),
+    ];
+
+    let full = Trace {
+        initial: failure.initial.clone(),
+        ops: failure.history.iter().map(|step| step.op.clone()).collect(),
+    };
+    let path = dir.join(format!("{}.trace", stem));
+    fs::write(&path, format(&full, &comments))?;
+    let mut paths = vec![path];
+
+    if let Some(minimized) = &failure.minimized {
+        let path = dir.join(format!("{}.min.trace", stem));
+        fs::write(&path, format(minimized, &comments))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::fuzz::{FuzzFailure, ImplOutcome};
+use crate::op::{ApplyError, Op};
+use crate::shrink;
+use crate::sut::LedgerUnderTest;
+
+/// How a failure diverged, independent of accounts and amounts: failures with
+/// the same signature are taken to be the same bug.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Signature {
+    /// Kind of the op the two sides disagreed on.
+    pub op: &'static str,
+    /// The model's verdict: `ok` or its error variant.
+    pub model: &'static str,
+    /// The implementation's verdict: `ok`, its error variant, or `panic`.
+    pub implementation: &'static str,
+    /// Which of result and state disagreed: `result`, `state` or
+    /// `result+state`; `harness-fault` when the Lean model and the Rust
+    /// reference disagreed, whatever the implementation did.
+    pub mismatch: &'static str,
+}
+
+impl Signature {
+    pub fn of(failure: &FuzzFailure) -> Self {
+        let op = match failure.op {
+            Op::Deposit { .. } => "deposit",
+            Op::Withdraw { .. } => "withdraw",
+            Op::Transfer { .. } => "transfer",
+        };
+        let implementation = match &failure.impl_outcome {
+            ImplOutcome::Returned(result) => verdict(result),
+            ImplOutcome::Panicked(_) => "panic",
+        };
+        let results_match = failure.impl_outcome.matches(&failure.model_result);
+        let state_match = failure
+            .history
+            .last()
+            .is_some_and(|step| step.model_snapshot == step.impl_snapshot);
+        let mismatch = match (results_match, state_match) {
+            _ if failure.harness_fault.is_some() => "harness-fault",
+            (false, false) => "result+state",
+            (false, true) => "result",
+            (true, _) => "state",
+        };
+        Self {
+            op,
+            model: verdict(&failure.model_result),
+            implementation,
+            mismatch,
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} model={} impl={} mismatch={}",
+            self.op, self.model, self.implementation, self.mismatch
+        )
+    }
+}
+
+/// Parses the `Display` form back, e.g.
+/// `withdraw model=ok impl=insufficient-funds mismatch=result+state`.
+impl FromStr for Signature {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid signature: {}", s.trim());
+        let mut parts = s.split_whitespace();
+        let op = parts.next().ok_or_else(invalid)?;
+        let mut field = |key: &str, values: &[&'static str]| {
+            parts
+                .next()
+                .and_then(|part| part.strip_prefix(key)?.strip_prefix('='))
+                .and_then(|value| values.iter().find(|known| **known == value).copied())
+                .ok_or_else(invalid)
+        };
+        let model = field("model", &VERDICTS[..3])?;
+        let implementation = field("impl", &VERDICTS)?;
+        let mismatch = field("mismatch", &MISMATCHES)?;
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            op: OPS.iter().find(|known| **known == op).ok_or_else(invalid)?,
+            model,
+            implementation,
+            mismatch,
+        })
+    }
+}
+
+const OPS: [&str; 3] = ["deposit", "withdraw", "transfer"];
+const VERDICTS: [&str; 4] = ["ok", "insufficient-funds", "overflow", "panic"];
+const MISMATCHES: [&str; 4] = ["result", "state", "result+state", "harness-fault"];
+
+fn verdict(result: &Result<(), ApplyError>) -> &'static str {
+    match result {
+        Ok(()) => "ok",
+        Err(ApplyError::InsufficientFunds { .. }) => "insufficient-funds",
+        Err(ApplyError::Overflow { .. }) => "overflow",
+    }
+}
+
+/// Every failure that shared one signature.
+#[derive(Clone, Debug)]
+pub struct Bucket {
+    pub signature: Signature,
+    /// How many failing cases landed here.
+    pub count: usize,
+    /// The case with the shortest history, minimized if shrinking is on.
+    pub representative: FuzzFailure,
+}
+
+/// Groups `failures` by signature, in order of first appearance, keeping the
+/// shortest failure of each group as its representative.
+pub fn dedup<'a>(failures: impl IntoIterator<Item = &'a FuzzFailure>) -> Vec<Bucket> {
+    let mut buckets = Vec::new();
+    for failure in failures {
+        add(&mut buckets, failure);
+    }
+    buckets
+}
+
+/// Counts `failure` into its bucket, cloning it only if it becomes the new
+/// representative.
+pub fn add(buckets: &mut Vec<Bucket>, failure: &FuzzFailure) {
+    let signature = Signature::of(failure);
+    match buckets
+        .iter_mut()
+        .find(|bucket| bucket.signature == signature)
+    {
+        Some(bucket) => {
+            bucket.count += 1;
+            if failure.history.len() < bucket.representative.history.len() {
+                bucket.representative = failure.clone();
+            }
+        }
+        None => buckets.push(Bucket {
+            signature,
+            count: 1,
+            representative: failure.clone(),
+        }),
+    }
+}
+
+pub fn shrink_representatives(buckets: &mut [Bucket], sut: &mut dyn LedgerUnderTest) {
+    for bucket in buckets {
+        bucket.representative.minimized = Some(shrink::shrink(&bucket.representative, sut));
+    }
+}
+
+/// The summary table printed after a `--keep-going` run.
+pub struct Summary<'a> {
+    pub buckets: &'a [Bucket],
+    pub cases: u64,
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed: usize = self.buckets.iter().map(|bucket| bucket.count).sum();
+        writeln!(
+            f,
+            "\n{} distinct failure(s) in {} of {} cases",
+            self.buckets.len(),
+            failed,
+            self.cases
+        )?;
+        writeln!(f, "----------------------------------------")?;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            let failure = &bucket.representative;
+            writeln!(
+                f,
+                "#{:<3} {:>5} case(s)  {}",
+                index + 1,
+                bucket.count,
+                bucket.signature
+            )?;
+            writeln!(
+                f,
+                "      replay   : cargo run -- replay {}",
+                failure.replay_args
+            )?;
+            match &failure.minimized {
+                Some(trace) => {
+                    let ops: Vec<String> = trace.ops.iter().map(|op| op.to_string()).collect();
+                    writeln!(f, "      initial  : {:?}", trace.initial)?;
+                    writeln!(f, "      ops      : {}", ops.join("; "))?;
+                }
+                None => writeln!(
+                    f,
+                    "      diverged : step {} of case {}: {}",
+                    failure.step_index, failure.case_index, failure.op
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a checked-in list of known failure signatures, one per line in their
+/// `Display` form. Blank lines and `#` comments are ignored.
+pub fn read_expectations(path: &Path) -> Result<Vec<Signature>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut signatures = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let signature = line
+            .parse::<Signature>()
+            .map_err(|e| format!("{}: line {}: {}", path.display(), index + 1, e))?;
+        signatures.push(signature);
+    }
+    Ok(signatures)
+}
+
+/// A run's failures held against the expected signatures.
+pub struct Gate<'a> {
+    pub known: Vec<&'a Bucket>,
+    pub new: Vec<&'a Bucket>,
+    /// Expected signatures no case hit. A run only samples the input space,
+    /// so this does not mean the bug behind one is fixed.
+    pub not_hit: Vec<&'a Signature>,
+}
+
+impl<'a> Gate<'a> {
+    pub fn check(buckets: &'a [Bucket], expected: &'a [Signature]) -> Self {
+        let (known, new) = buckets
+            .iter()
+            .partition(|bucket| expected.contains(&bucket.signature));
+        let not_hit = expected
+            .iter()
+            .filter(|signature| !buckets.iter().any(|bucket| bucket.signature == **signature))
+            .collect();
+        Self {
+            known,
+            new,
+            not_hit,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.new.is_empty()
+    }
+}
+
+impl fmt::Display for Gate<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "\nexpectations: {} known, {} new, {} not hit",
+            self.known.len(),
+            self.new.len(),
+            self.not_hit.len()
+        )?;
+        for bucket in &self.known {
+            writeln!(f, "  known : {}", bucket.signature)?;
+        }
+        for bucket in &self.new {
+            writeln!(f, "  new   : {}", bucket.signature)?;
+        }
+        for signature in &self.not_hit {
+            writeln!(f, "  not hit: {} (not hit in this run)", signature)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+// Checks the `--sut-http` path: URL and template parsing, the adapter
+// against a server that answers with canned responses, and fuzzing through
+// `LoopbackServer`, which serves the same `Ledger` the harness tests
+// in-process.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use verified_ledger::fuzz::{self, FuzzConfig, RunControl, RunReport};
+use verified_ledger::ledger::Ledger;
+use verified_ledger::op::{ApplyError, Op};
+use verified_ledger::sut::http::{HttpLedger, LoopbackServer, Template, Templates, Url};
+use verified_ledger::sut::process::DEFAULT_TIMEOUT;
+use verified_ledger::sut::{Implementation, LedgerUnderTest};
+use verified_ledger::triage::Signature;
+
+fn deposit(account: &str, amount: u64) -> Op {
+    Op::Deposit {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn withdraw(account: &str, amount: u64) -> Op {
+    Op::Withdraw {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn transfer(from: &str, to: &str, amount: u64) -> Op {
+    Op::Transfer {
+        from: from.to_string(),
+        to: to.to_string(),
+        amount,
+    }
+}
+
+#[test]
+fn urls_parse_and_display_with_their_port() {
+    for (text, shown) in [
+        ("http://localhost", "http://localhost:80"),
+        ("http://localhost/", "http://localhost:80"),
+        ("http://127.0.0.1:8080/api/", "http://127.0.0.1:8080/api"),
+        ("http://example.com:1/a/b", "http://example.com:1/a/b"),
+        ("http://[::1]", "http://[::1]:80"),
+        ("http://[::1]:8080/api/", "http://[::1]:8080/api"),
+        ("http://[fe80::1:2]:65535", "http://[fe80::1:2]:65535"),
+    ] {
+        let url = text.parse::<Url>().unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(url.to_string(), shown, "from {}", text);
+        assert_eq!(shown.parse::<Url>(), Ok(url), "from {}", shown);
+    }
+
+    for text in [
+        "",
+        "localhost:80",
+        "https://localhost",
+        "http://",
+        "http://:80",
+        "http://localhost:",
+        "http://localhost:http",
+        "http://localhost:65536",
+        "http://::1:8080",
+        "http://[::1",
+        "http://[::1]8080",
+        "http://[::1]:",
+        "http://[]:80",
+    ] {
+        assert!(text.parse::<Url>().is_err(), "{:?} parsed", text);
+    }
+}
+
+#[test]
+fn templates_parse_and_display() {
+    let defaults = Templates::default();
+    for template in [
+        &defaults.deposit,
+        &defaults.withdraw,
+        &defaults.transfer,
+        &defaults.balances,
+        &defaults.reset,
+    ] {
+        assert_eq!(template.to_string().parse(), Ok(template.clone()));
+    }
+    assert_eq!(defaults.overrides(), Vec::<String>::new());
+
+    let template: Template = "  PUT /accounts/{account}   {\"add\": {amount}}  "
+        .parse()
+        .expect("template parses");
+    assert_eq!(
+        template.to_string(),
+        "PUT /accounts/{account} {\"add\": {amount}}"
+    );
+    let mut templates = Templates::default();
+    *templates.get_mut("deposit").expect("a template key") = template;
+    assert_eq!(
+        templates.overrides(),
+        ["deposit=PUT /accounts/{account} {\"add\": {amount}}"]
+    );
+    assert!(templates.get_mut("mint").is_none());
+
+    for text in ["", "POST", "post /a", "POST a", "P0ST /a", "POST  /a"] {
+        assert!(text.parse::<Template>().is_err(), "{:?} parsed", text);
+    }
+}
+
+/// What the canned server expects next: the end of a request line, the
+/// request body, and the raw response to write back. An empty response
+/// closes the connection without answering.
+type Exchange = (&'static str, &'static str, &'static str);
+
+struct CannedServer {
+    url: Url,
+    done: mpsc::Sender<()>,
+    thread: thread::JoinHandle<Vec<String>>,
+}
+
+impl CannedServer {
+    /// Serves one connection per entry of `connections`, each with its
+    /// exchanges in order, closing it after the last.
+    fn start(connections: Vec<Vec<Exchange>>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind a local port");
+        let url = format!("http://{}/", listener.local_addr().expect("bound address"))
+            .parse()
+            .expect("a loopback URL");
+        let (done, finished) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut seen = Vec::new();
+            for exchanges in connections {
+                let (stream, _) = listener.accept().expect("a connection");
+                serve_canned(stream, exchanges, &mut seen);
+            }
+            finished.recv().expect("the test finishes");
+            listener
+                .set_nonblocking(true)
+                .expect("a non-blocking listener");
+            match listener.accept() {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Ok(_) => panic!("a connection beyond the script, after {:?}", seen),
+                Err(e) => panic!("accept failed: {}", e),
+            }
+            seen
+        });
+        Self { url, done, thread }
+    }
+
+    /// The request lines the server read, once the client is done with it.
+    fn finish(self) -> Vec<String> {
+        self.done.send(()).expect("the server is waiting");
+        self.thread.join().expect("the canned server")
+    }
+}
+
+fn serve_canned(stream: TcpStream, exchanges: Vec<Exchange>, seen: &mut Vec<String>) {
+    let mut writer = stream.try_clone().expect("clone the stream");
+    let mut reader = BufReader::new(stream);
+    for (line_end, body, response) in exchanges {
+        let mut line = String::new();
+        reader.read_line(&mut line).expect("a request line");
+        let line = line.trim_end().to_string();
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).expect("a header");
+            let header = header.trim_end().to_ascii_lowercase();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("content-length:") {
+                length = value.trim().parse().expect("a numeric length");
+            }
+        }
+        let mut received = vec![0; length];
+        reader.read_exact(&mut received).expect("the request body");
+        assert!(line.ends_with(line_end), "{:?} for {:?}", line, line_end);
+        assert_eq!(String::from_utf8_lossy(&received), body, "body of {}", line);
+        seen.push(line);
+        if response.is_empty() {
+            return;
+        }
+        writer
+            .write_all(response.as_bytes())
+            .expect("write a response");
+    }
+}
+
+fn panic_message<T: std::fmt::Debug>(f: impl FnOnce() -> T) -> String {
+    let payload = panic::catch_unwind(AssertUnwindSafe(f)).expect_err("a panic");
+    payload
+        .downcast_ref::<String>()
+        .expect("a formatted panic message")
+        .clone()
+}
+
+#[test]
+fn keep_alive_chunked_and_bodiless_responses_share_one_connection() {
+    let server = CannedServer::start(vec![vec![
+        ("/reset HTTP/1.1", "", "HTTP/1.1 204 No Content\r\n\r\n"),
+        (
+            "/deposit HTTP/1.1",
+            r#"{"account":"a/{b}","amount":5}"#,
+            concat!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+                "4\r\n{\"ok\r\n",
+                "7;ext=1\r\n\":true}\r\n",
+                "0\r\nX-Trailer: yes\r\n\r\n",
+            ),
+        ),
+        (
+            "/balances HTTP/1.1",
+            "",
+            concat!(
+                "HTTP/1.1 100 Continue\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+                "1a\r\n{\"balances\":[[\"a/{b}\",5]]}\r\n",
+                "0\r\n\r\n",
+            ),
+        ),
+        (
+            "/withdraw HTTP/1.1",
+            r#"{"account":"bob","amount":1}"#,
+            concat!(
+                "HTTP/1.1 409 Conflict\r\nContent-Length: 94\r\n\r\n",
+                r#"{"ok":false,"error":"insufficient-funds","account":"bob","balance":0,"amount":1,"balances":[]}"#,
+            ),
+        ),
+        (
+            "/reset HTTP/1.1",
+            "",
+            "HTTP/1.1 204 No Content\r\nContent-Length: 9\r\nConnection: close\r\n\r\n",
+        ),
+    ]]);
+
+    let mut http = HttpLedger::new(server.url.clone(), Templates::default(), DEFAULT_TIMEOUT);
+    http.reset();
+    assert_eq!(http.apply(&deposit("a/{b}", 5)), Ok(()));
+    assert_eq!(http.snapshot(), [("a/{b}".to_string(), 5)]);
+    assert_eq!(
+        http.apply(&withdraw("bob", 1)),
+        Err(ApplyError::InsufficientFunds {
+            account: "bob".to_string(),
+            balance: 0,
+            amount: 1,
+        })
+    );
+    // the op's reply carried the balances, so nothing is asked for them
+    assert_eq!(http.snapshot(), Vec::new());
+    http.reset();
+
+    let seen = server.finish();
+    assert_eq!(seen.len(), 5);
+    assert!(
+        seen[1].starts_with("POST /ledgers/") && seen[1].contains("/deposit"),
+        "{}",
+        seen[1]
+    );
+    assert!(seen[2].starts_with("GET "), "{}", seen[2]);
+}
+
+#[test]
+fn server_errors_panic() {
+    let server = CannedServer::start(vec![vec![(
+        "/deposit HTTP/1.1",
+        r#"{"account":"alice","amount":1}"#,
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\n\r\nbusy",
+    )]]);
+    let mut http = HttpLedger::new(server.url.clone(), Templates::default(), DEFAULT_TIMEOUT);
+    let message = panic_message(|| http.apply(&deposit("alice", 1)));
+    assert!(message.contains("status 503: busy"), "{}", message);
+    server.finish();
+}
+
+#[test]
+fn balances_come_out_sorted_whatever_order_the_server_lists_them_in() {
+    let unsorted = concat!(
+        "HTTP/1.1 200 OK\r\nContent-Length: 45\r\n\r\n",
+        r#"{"balances":[["b",2],["",3],["a",1],["c",4]]}"#,
+    );
+    let server = CannedServer::start(vec![vec![
+        ("/reset HTTP/1.1", "", "HTTP/1.1 204 No Content\r\n\r\n"),
+        (
+            "/deposit HTTP/1.1",
+            r#"{"account":"a","amount":1}"#,
+            concat!(
+                "HTTP/1.1 200 OK\r\nContent-Length: 40\r\n\r\n",
+                r#"{"ok":true,"balances":[["b",2],["a",1]]}"#,
+            ),
+        ),
+        ("/reset HTTP/1.1", "", "HTTP/1.1 204 No Content\r\n\r\n"),
+        (
+            "/deposit HTTP/1.1",
+            r#"{"account":"a","amount":1}"#,
+            "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"ok\":true}",
+        ),
+        ("/balances HTTP/1.1", "", unsorted),
+    ]]);
+    let mut http = HttpLedger::new(server.url.clone(), Templates::default(), DEFAULT_TIMEOUT);
+    let pairs = |pairs: &[(&str, u64)]| -> Vec<(String, u64)> {
+        pairs
+            .iter()
+            .map(|(account, balance)| (account.to_string(), *balance))
+            .collect()
+    };
+    http.reset();
+    assert_eq!(http.apply(&deposit("a", 1)), Ok(()));
+    assert_eq!(http.snapshot(), pairs(&[("a", 1), ("b", 2)]));
+    http.reset();
+    assert_eq!(http.apply(&deposit("a", 1)), Ok(()));
+    assert_eq!(
+        http.snapshot(),
+        pairs(&[("", 3), ("a", 1), ("b", 2), ("c", 4)])
+    );
+    server.finish();
+}
+
+#[test]
+fn ops_are_not_resent_once_the_server_may_have_applied_them() {
+    let ok = "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"ok\":true}";
+    let balances = "HTTP/1.1 200 OK\r\nContent-Length: 22\r\n\r\n{\"balances\":[[\"a\",1]]}";
+    let server = CannedServer::start(vec![
+        // the deposit reaches the server, which closes without answering
+        vec![
+            ("/reset HTTP/1.1", "", "HTTP/1.1 204 No Content\r\n\r\n"),
+            ("/deposit HTTP/1.1", r#"{"account":"a","amount":1}"#, ""),
+        ],
+        // a balances read that gets no answer is safe to send again
+        vec![
+            ("/reset HTTP/1.1", "", "HTTP/1.1 204 No Content\r\n\r\n"),
+            ("/deposit HTTP/1.1", r#"{"account":"a","amount":1}"#, ok),
+            ("/balances HTTP/1.1", "", ""),
+        ],
+        vec![("/balances HTTP/1.1", "", balances)],
+        // a connection closed while idle is replaced before the next op
+        vec![("/deposit HTTP/1.1", r#"{"account":"a","amount":2}"#, ok)],
+    ]);
+    // without Connection: close, so the adapter keeps it for the next op
+    let mut http = HttpLedger::new(server.url.clone(), Templates::default(), DEFAULT_TIMEOUT);
+    http.reset();
+    let message = panic_message(|| http.apply(&deposit("a", 1)));
+    assert!(message.contains("POST /ledgers/"), "{}", message);
+
+    http.reset();
+    assert_eq!(http.apply(&deposit("a", 1)), Ok(()));
+    // the op reply carried no balances, so they are asked for
+    assert_eq!(http.snapshot(), [("a".to_string(), 1)]);
+    // the server hangs up after answering; give the close time to arrive
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(http.apply(&deposit("a", 2)), Ok(()));
+
+    let seen = server.finish();
+    assert_eq!(
+        seen.iter().filter(|line| line.contains("/deposit")).count(),
+        3
+    );
+}
+
+#[test]
+fn loopback_agrees_with_ledger_op_by_op() {
+    // IPv6 only where the host has it
+    let servers = ["127.0.0.1:0", "[::1]:0"]
+        .into_iter()
+        .filter_map(|addr| LoopbackServer::start(addr).ok());
+    for server in servers {
+        let url = server.url();
+        assert_eq!(url.to_string().parse(), Ok(url.clone()));
+        let mut http = HttpLedger::new(url, Templates::default(), DEFAULT_TIMEOUT);
+        let mut ledger = Ledger::new();
+        http.check().expect("the loopback server resets");
+        http.reset();
+        let ops = [
+            deposit("alice", 10),
+            deposit("b o b", 3),
+            deposit("", 4),
+            deposit("{amount}", 1),
+            deposit("zoë", u64::MAX),
+            deposit("zoë", 1),
+            withdraw("alice", 11),
+            withdraw("alice", 10),
+            transfer("", "alice", 2),
+            transfer("b o b", "{amount}", 1),
+            transfer("zoë", "zoë", 5),
+        ];
+        for op in &ops {
+            assert_eq!(http.apply(op), ledger.apply(op), "result of {}", op);
+            assert_eq!(http.snapshot(), ledger.snapshot(), "state after {}", op);
+        }
+        http.reset();
+        assert_eq!(http.snapshot(), Vec::new());
+    }
+}
+
+fn fuzz(implementation: Implementation, jobs: usize) -> RunReport {
+    let mut config = FuzzConfig::new(0x5eed);
+    config.cases = 30;
+    config.jobs = jobs;
+    config.implementation = implementation;
+    let stop = AtomicBool::new(false);
+    let control = RunControl {
+        stop: &stop,
+        progress_every: None,
+        on_progress: &|_| {},
+    };
+    fuzz::run(&config, true, &control)
+}
+
+fn signatures(report: &RunReport) -> Vec<(Signature, usize)> {
+    report
+        .buckets
+        .as_deref()
+        .expect("keep-going runs bucket their failures")
+        .iter()
+        .map(|bucket| (bucket.signature.clone(), bucket.count))
+        .collect()
+}
+
+// a single test drives the Lean model, from one thread, since the runtime
+// takes whichever thread initializes it first as its main thread
+#[test]
+fn fuzzing_through_the_loopback_server_matches_in_process_runs() {
+    let server = LoopbackServer::start("127.0.0.1:0").expect("bind a local port");
+    let in_process = fuzz(Implementation::default(), 1);
+    assert!(
+        in_process.totals.failed > 0,
+        "the seeded Ledger bugs should be found"
+    );
+
+    for jobs in [1, 3] {
+        let url = server.url();
+        let through_http = fuzz(
+            Implementation::new(move || {
+                Box::new(HttpLedger::new(
+                    url.clone(),
+                    Templates::default(),
+                    DEFAULT_TIMEOUT,
+                ))
+            }),
+            jobs,
+        );
+        assert!(through_http.implementation.starts_with("http "));
+        assert_eq!(through_http.totals.cases, in_process.totals.cases);
+        assert_eq!(through_http.totals.failed, in_process.totals.failed);
+        assert_eq!(through_http.totals.steps, in_process.totals.steps);
+        assert_eq!(signatures(&through_http), signatures(&in_process));
+    }
+}
@@ -0,0 +1,159 @@
+// Checks the `--sut-lib` path end to end against the reference plugin in
+// `examples/ledger_plugin.rs`, which wraps the same `Ledger` the harness
+// tests in-process.
+
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use verified_ledger::fuzz::{self, FuzzConfig, RunControl, RunReport};
+use verified_ledger::ledger::Ledger;
+use verified_ledger::op::{ApplyError, Op};
+use verified_ledger::sut::plugin::{Plugin, PluginLedger};
+use verified_ledger::sut::{Implementation, LedgerUnderTest};
+use verified_ledger::triage::Signature;
+
+/// Builds the reference plugin and loads it. `cargo test` builds examples
+/// already, but not for `cargo test --test plugin`.
+fn reference_plugin() -> Arc<Plugin> {
+    // target/<profile>/deps/plugin-<hash> -> target/<profile>/examples
+    let exe = std::env::current_exe().expect("test executable path");
+    let profile_dir = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("test executable under target/<profile>/deps");
+
+    let mut build = Command::new(env!("CARGO"));
+    build
+        .args(["build", "--quiet", "--example", "ledger_plugin"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"));
+    if profile_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    if !cfg!(feature = "lean-model") {
+        build.arg("--no-default-features");
+    }
+    let status = build.status().expect("failed to run cargo");
+    assert!(
+        status.success(),
+        "cargo build --example ledger_plugin failed"
+    );
+
+    let path: PathBuf = profile_dir.join("examples").join(format!(
+        "{}ledger_plugin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    Plugin::load(&path).unwrap_or_else(|message| panic!("{}", message))
+}
+
+fn deposit(account: &str, amount: u64) -> Op {
+    Op::Deposit {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn withdraw(account: &str, amount: u64) -> Op {
+    Op::Withdraw {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn transfer(from: &str, to: &str, amount: u64) -> Op {
+    Op::Transfer {
+        from: from.to_string(),
+        to: to.to_string(),
+        amount,
+    }
+}
+
+#[test]
+fn plugin_agrees_with_ledger_op_by_op() {
+    let mut plugin = PluginLedger::new(reference_plugin());
+    let mut ledger = Ledger::new();
+    let ops = [
+        deposit("alice", 10),
+        deposit("", 3),
+        deposit("zoë", u64::MAX),
+        deposit("zoë", 1),
+        withdraw("alice", 11),
+        withdraw("alice", 10),
+        transfer("", "alice", 2),
+        transfer("bob", "alice", 1),
+        transfer("zoë", "zoë", 5),
+    ];
+    for op in &ops {
+        assert_eq!(plugin.apply(op), ledger.apply(op), "result of {}", op);
+        assert_eq!(plugin.snapshot(), ledger.snapshot(), "state after {}", op);
+    }
+    assert!(matches!(
+        plugin.apply(&withdraw("bob", 1)),
+        Err(ApplyError::InsufficientFunds { account, balance: 0, amount: 1 }) if account == "bob"
+    ));
+
+    plugin.reset();
+    assert_eq!(plugin.snapshot(), Vec::new());
+}
+
+#[test]
+fn missing_library_is_an_error() {
+    let error = match Plugin::load("/nonexistent/libledger.so".as_ref()) {
+        Ok(_) => panic!("loaded a library that does not exist"),
+        Err(error) => error,
+    };
+    assert!(error.contains("/nonexistent/libledger.so"), "{}", error);
+}
+
+fn fuzz(implementation: Implementation, jobs: usize) -> RunReport {
+    let mut config = FuzzConfig::new(0x5eed);
+    config.cases = 30;
+    config.jobs = jobs;
+    config.implementation = implementation;
+    let stop = AtomicBool::new(false);
+    let control = RunControl {
+        stop: &stop,
+        progress_every: None,
+        on_progress: &|_| {},
+    };
+    fuzz::run(&config, true, &control)
+}
+
+fn signatures(report: &RunReport) -> Vec<(Signature, usize)> {
+    report
+        .buckets
+        .as_deref()
+        .expect("keep-going runs bucket their failures")
+        .iter()
+        .map(|bucket| (bucket.signature.clone(), bucket.count))
+        .collect()
+}
+
+// a single test drives the Lean model, from one thread, since the runtime
+// takes whichever thread initializes it first as its main thread
+#[test]
+fn fuzzing_through_the_plugin_matches_in_process_runs() {
+    let plugin = reference_plugin();
+    let in_process = fuzz(Implementation::default(), 1);
+    assert!(
+        in_process.totals.failed > 0,
+        "the seeded Ledger bugs should be found"
+    );
+
+    for jobs in [1, 3] {
+        let plugin = plugin.clone();
+        let through_plugin = fuzz(
+            Implementation::new(move || Box::new(PluginLedger::new(plugin.clone()))),
+            jobs,
+        );
+        assert!(through_plugin.implementation.starts_with("plugin "));
+        assert_eq!(through_plugin.totals.cases, in_process.totals.cases);
+        assert_eq!(through_plugin.totals.failed, in_process.totals.failed);
+        assert_eq!(through_plugin.totals.steps, in_process.totals.steps);
+        assert_eq!(signatures(&through_plugin), signatures(&in_process));
+    }
+}
@@ -0,0 +1,305 @@
+// Checks the `--sut-process` path end to end against the reference process in
+// `examples/ledger_stub.rs`, which wraps the same `Ledger` the harness tests
+// in-process, including its `--exit-after` and `--hang-after` failure modes.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use verified_ledger::fuzz::{self, FuzzConfig, ImplOutcome, OpWeights, RunControl, RunReport};
+use verified_ledger::ledger::Ledger;
+use verified_ledger::op::{ApplyError, Op};
+use verified_ledger::sut::process::{
+    self, decode_reply, decode_request, encode_reply, encode_request, ProcessLedger, Reply, Request,
+};
+use verified_ledger::sut::{Implementation, LedgerUnderTest};
+use verified_ledger::triage::Signature;
+
+/// Builds the reference process and returns the command that runs it with
+/// `args`. `cargo test` builds examples already, but not for
+/// `cargo test --test process`.
+fn ledger_stub(args: &[&str]) -> Vec<String> {
+    // target/<profile>/deps/process-<hash> -> target/<profile>/examples
+    let exe = std::env::current_exe().expect("test executable path");
+    let profile_dir = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .expect("test executable under target/<profile>/deps");
+
+    let mut build = Command::new(env!("CARGO"));
+    build
+        .args(["build", "--quiet", "--example", "ledger_stub"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"));
+    if profile_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    if !cfg!(feature = "lean-model") {
+        build.arg("--no-default-features");
+    }
+    let status = build.status().expect("failed to run cargo");
+    assert!(status.success(), "cargo build --example ledger_stub failed");
+
+    let path = profile_dir
+        .join("examples")
+        .join(format!("ledger_stub{}", std::env::consts::EXE_SUFFIX));
+    let mut command = vec![path.display().to_string()];
+    command.extend(args.iter().map(|arg| arg.to_string()));
+    command
+}
+
+fn deposit(account: &str, amount: u64) -> Op {
+    Op::Deposit {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn withdraw(account: &str, amount: u64) -> Op {
+    Op::Withdraw {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn transfer(from: &str, to: &str, amount: u64) -> Op {
+    Op::Transfer {
+        from: from.to_string(),
+        to: to.to_string(),
+        amount,
+    }
+}
+
+#[test]
+fn requests_round_trip() {
+    let names = ["alice", "", "b o b", "say \"hi\"", "tab\t\\", "\0", "zoë"];
+    let mut requests = vec![Request::Reset];
+    for name in names {
+        requests.push(Request::Apply(deposit(name, u64::MAX)));
+        requests.push(Request::Apply(withdraw(name, 0)));
+        requests.push(Request::Apply(transfer(name, "alice", 5)));
+        requests.push(Request::Apply(transfer("alice", name, 1 << 60)));
+    }
+    for request in requests {
+        let line = encode_request(&request);
+        assert!(!line.contains('\n'), "{:?} spans lines", line);
+        assert_eq!(decode_request(&line), Ok(request), "from {}", line);
+    }
+
+    for line in [
+        "",
+        "{}",
+        r#"{"kind":"mint","account":"alice","amount":5}"#,
+        r#"{"kind":"deposit","account":"alice"}"#,
+        r#"{"kind":"deposit","account":"alice","amount":-5}"#,
+        r#"{"kind":"deposit","account":7,"amount":5}"#,
+        r#"{"kind":"transfer","from":"alice","amount":5}"#,
+    ] {
+        assert!(decode_request(line).is_err(), "{} decoded", line);
+    }
+}
+
+#[test]
+fn replies_round_trip() {
+    let balances = vec![
+        ("".to_string(), 1),
+        ("alice".to_string(), u64::MAX),
+        ("b o b".to_string(), 3),
+    ];
+    let replies = [
+        Reply {
+            result: Ok(()),
+            balances: Vec::new(),
+        },
+        Reply {
+            result: Ok(()),
+            balances: balances.clone(),
+        },
+        Reply {
+            result: Err(ApplyError::InsufficientFunds {
+                account: "say \"hi\"".to_string(),
+                balance: 0,
+                amount: u64::MAX,
+            }),
+            balances: balances.clone(),
+        },
+        Reply {
+            result: Err(ApplyError::Overflow {
+                account: "alice".to_string(),
+                balance: u64::MAX,
+                amount: 1,
+            }),
+            balances,
+        },
+    ];
+    for reply in replies {
+        let line = encode_reply(&reply);
+        assert!(!line.contains('\n'), "{:?} spans lines", line);
+        assert_eq!(decode_reply(&line), Ok(reply), "from {}", line);
+    }
+
+    for line in [
+        r#"{"ok":true}"#,
+        r#"{"ok":"yes","balances":[]}"#,
+        r#"{"ok":false,"balances":[]}"#,
+        r#"{"ok":false,"error":"oops","account":"a","balance":0,"amount":1,"balances":[]}"#,
+        r#"{"ok":true,"balances":[["alice"]]}"#,
+        r#"{"ok":true,"balances":[["alice",-1]]}"#,
+    ] {
+        assert!(decode_reply(line).is_err(), "{} decoded", line);
+    }
+}
+
+#[test]
+fn process_agrees_with_ledger_op_by_op() {
+    // snapshots come out sorted whatever order the process lists them in
+    for args in [&[][..], &["--reverse-balances"]] {
+        agrees_with_ledger_op_by_op(ProcessLedger::new(
+            ledger_stub(args),
+            process::DEFAULT_TIMEOUT,
+        ));
+    }
+}
+
+fn agrees_with_ledger_op_by_op(mut stub: ProcessLedger) {
+    let mut ledger = Ledger::new();
+    stub.check().expect("the stub resets");
+    let ops = [
+        deposit("alice", 10),
+        deposit("b o b", 3),
+        deposit("", 4),
+        deposit("zoë", u64::MAX),
+        deposit("zoë", 1),
+        withdraw("alice", 11),
+        withdraw("alice", 10),
+        transfer("", "alice", 2),
+        transfer("b o b", "alice", 1),
+        transfer("zoë", "zoë", 5),
+    ];
+    for op in &ops {
+        assert_eq!(stub.apply(op), ledger.apply(op), "result of {}", op);
+        assert_eq!(stub.snapshot(), ledger.snapshot(), "state after {}", op);
+    }
+
+    stub.reset();
+    assert_eq!(stub.snapshot(), Vec::new());
+    assert_eq!(stub.apply(&deposit("alice", 1)), Ok(()));
+    assert_eq!(stub.snapshot(), [("alice".to_string(), 1)]);
+}
+
+#[test]
+fn crashes_and_hangs_panic_and_reset_restarts() {
+    for (args, expected) in [
+        (["--exit-after", "2"], "exited"),
+        (["--hang-after", "2"], "no reply within"),
+    ] {
+        let mut stub = ProcessLedger::new(ledger_stub(&args), Duration::from_millis(200));
+        stub.reset();
+        assert_eq!(stub.apply(&deposit("alice", 5)), Ok(()));
+        let message = panic::catch_unwind(AssertUnwindSafe(|| stub.apply(&deposit("alice", 1))))
+            .expect_err("the third request fails");
+        let message = message
+            .downcast_ref::<String>()
+            .expect("a formatted panic message");
+        assert!(message.contains(expected), "{}", message);
+
+        // a fresh process, so two more requests before it fails again
+        stub.reset();
+        assert_eq!(stub.snapshot(), Vec::new());
+        assert_eq!(stub.apply(&deposit("bob", 2)), Ok(()));
+        assert_eq!(stub.snapshot(), [("bob".to_string(), 2)]);
+    }
+}
+
+fn fuzz(config: FuzzConfig) -> RunReport {
+    let stop = AtomicBool::new(false);
+    let control = RunControl {
+        stop: &stop,
+        progress_every: None,
+        on_progress: &|_| {},
+    };
+    fuzz::run(&config, true, &control)
+}
+
+fn config(implementation: Implementation, jobs: usize) -> FuzzConfig {
+    let mut config = FuzzConfig::new(0x5eed);
+    config.cases = 30;
+    config.jobs = jobs;
+    config.implementation = implementation;
+    config
+}
+
+fn signatures(report: &RunReport) -> Vec<(Signature, usize)> {
+    report
+        .buckets
+        .as_deref()
+        .expect("keep-going runs bucket their failures")
+        .iter()
+        .map(|bucket| (bucket.signature.clone(), bucket.count))
+        .collect()
+}
+
+fn through_stub(args: &'static [&'static str], timeout: Duration) -> Implementation {
+    let command = ledger_stub(args);
+    Implementation::new(move || Box::new(ProcessLedger::new(command.clone(), timeout)))
+}
+
+// a single test drives the Lean model, from one thread, since the runtime
+// takes whichever thread initializes it first as its main thread
+#[test]
+fn fuzzing_through_the_process_matches_in_process_runs() {
+    let in_process = fuzz(config(Implementation::default(), 1));
+    assert!(
+        in_process.totals.failed > 0,
+        "the seeded Ledger bugs should be found"
+    );
+    for jobs in [1, 3] {
+        let through_process = fuzz(config(through_stub(&[], process::DEFAULT_TIMEOUT), jobs));
+        assert!(through_process.implementation.starts_with("process `"));
+        assert_eq!(through_process.totals.cases, in_process.totals.cases);
+        assert_eq!(through_process.totals.failed, in_process.totals.failed);
+        assert_eq!(through_process.totals.steps, in_process.totals.steps);
+        assert_eq!(signatures(&through_process), signatures(&in_process));
+    }
+
+    // deposits alone never hit the Ledger bugs, so every failure here is the
+    // process going away. A case needs 1 reset, a seeding deposit per
+    // initial balance and 20 ops, so a fresh process gets through one case
+    // and dies in the next; the case after that starts a new one and passes
+    for (args, expected) in [
+        (&["--exit-after", "40"], "exited"),
+        (&["--hang-after", "40"], "no reply within"),
+    ] {
+        let mut config = config(through_stub(args, Duration::from_millis(200)), 1);
+        config.cases = 8;
+        config.steps = 20;
+        config.op_weights = OpWeights::from_weights([1, 0, 0]);
+        config.shrink = false;
+        let report = fuzz(config);
+        assert_eq!(report.totals.cases, 8);
+        assert!(report.totals.failed > 0, "{:?} never failed", args);
+        let passed: Vec<bool> = report
+            .cases
+            .iter()
+            .map(|case| case.outcome.is_ok())
+            .collect();
+        assert!(passed[0], "{:?}", passed);
+        for (index, failure) in report.failures().enumerate() {
+            match &failure.impl_outcome {
+                ImplOutcome::Panicked(message) => {
+                    assert!(message.contains(expected), "{}", message)
+                }
+                ImplOutcome::Returned(result) => panic!("failure {} returned {:?}", index, result),
+            }
+            let case = failure.case_index as usize;
+            if case + 1 < passed.len() {
+                assert!(
+                    passed[case + 1],
+                    "case {} after a crash: {:?}",
+                    case + 1,
+                    passed
+                );
+            }
+        }
+    }
+}
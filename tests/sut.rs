@@ -0,0 +1,136 @@
+// Checks that an implementation panicking outside `apply` (while resetting,
+// or snapshotting before or after an op) fails the step it happens in rather
+// than unwinding through the run.
+
+use std::sync::atomic::AtomicBool;
+
+use verified_ledger::fuzz::{self, FuzzConfig, FuzzFailure, ImplOutcome, RunControl, Trace};
+use verified_ledger::ledger::Ledger;
+use verified_ledger::op::{ApplyError, Op};
+use verified_ledger::sut::{Implementation, LedgerUnderTest};
+use verified_ledger::triage::Signature;
+
+/// `Ledger`, except that the `panic_on_snapshot`-th snapshot since the last
+/// reset panics, and so does `reset` if `panic_on_reset` is set.
+struct Flaky {
+    ledger: Ledger,
+    snapshots: usize,
+    panic_on_snapshot: Option<usize>,
+    panic_on_reset: bool,
+}
+
+impl Flaky {
+    fn new(panic_on_snapshot: Option<usize>, panic_on_reset: bool) -> Self {
+        Self {
+            ledger: Ledger::new(),
+            snapshots: 0,
+            panic_on_snapshot,
+            panic_on_reset,
+        }
+    }
+}
+
+impl LedgerUnderTest for Flaky {
+    fn name(&self) -> &str {
+        "flaky"
+    }
+
+    fn apply(&mut self, op: &Op) -> Result<(), ApplyError> {
+        self.ledger.apply(op)
+    }
+
+    fn snapshot(&mut self) -> Vec<(String, u64)> {
+        self.snapshots += 1;
+        if self.panic_on_snapshot == Some(self.snapshots) {
+            panic!("snapshot {} failed", self.snapshots);
+        }
+        self.ledger.snapshot()
+    }
+
+    fn reset(&mut self) {
+        if self.panic_on_reset {
+            panic!("reset failed");
+        }
+        self.ledger = Ledger::new();
+        self.snapshots = 0;
+    }
+}
+
+fn deposit(account: &str, amount: u64) -> Op {
+    Op::Deposit {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+// each step snapshots the implementation before and after its op
+fn replay(mut flaky: Flaky) -> FuzzFailure {
+    let trace = Trace {
+        initial: Vec::new(),
+        ops: vec![deposit("a", 5), deposit("b", 3), deposit("a", 1)],
+    };
+    *fuzz::replay_trace(&trace, 0, 0, &mut flaky).expect_err("the implementation panicked")
+}
+
+fn panic_message(failure: &FuzzFailure) -> &str {
+    match &failure.impl_outcome {
+        ImplOutcome::Panicked(message) => message,
+        ImplOutcome::Returned(result) => panic!("expected a panic, got {:?}", result),
+    }
+}
+
+// a single test drives the Lean model, from one thread, since the runtime
+// takes whichever thread initializes it first as its main thread
+#[test]
+fn panics_outside_apply_fail_the_step() {
+    let after = replay(Flaky::new(Some(4), false));
+    assert_eq!(after.step_index, 1);
+    assert!(
+        panic_message(&after).starts_with("snapshot after the op: snapshot 4 failed"),
+        "{}",
+        panic_message(&after)
+    );
+    assert_eq!(after.impl_pre_snapshot, [("a".to_string(), 5)]);
+    assert_eq!(
+        Signature::of(&after).to_string(),
+        "deposit model=ok impl=panic mismatch=result+state"
+    );
+
+    let before = replay(Flaky::new(Some(3), false));
+    assert_eq!(before.step_index, 1);
+    assert!(
+        panic_message(&before).starts_with("snapshot before the op: snapshot 3 failed"),
+        "{}",
+        panic_message(&before)
+    );
+    assert_eq!(before.history.len(), 2);
+
+    let reset = replay(Flaky::new(None, true));
+    assert_eq!(reset.step_index, 0);
+    assert!(
+        panic_message(&reset).starts_with("reset: reset failed"),
+        "{}",
+        panic_message(&reset)
+    );
+
+    // a whole run survives it too, and every case resets and fails again
+    let mut config = FuzzConfig::new(0x5eed);
+    config.cases = 5;
+    config.implementation = Implementation::new(|| Box::new(Flaky::new(Some(2), false)));
+    let stop = AtomicBool::new(false);
+    let control = RunControl {
+        stop: &stop,
+        progress_every: None,
+        on_progress: &|_| {},
+    };
+    let report = fuzz::run(&config, true, &control);
+    assert_eq!(report.totals.cases, 5);
+    assert_eq!(report.totals.failed, 5);
+    for failure in report.failures() {
+        assert!(
+            panic_message(failure).contains("snapshot 2 failed"),
+            "{}",
+            failure
+        );
+    }
+}
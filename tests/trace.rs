@@ -0,0 +1,145 @@
+// Round-trips ops and trace files through their text forms, with the account
+// names that need quoting and escapes.
+
+use verified_ledger::fuzz::Trace;
+use verified_ledger::op::{AccountName, Op};
+use verified_ledger::trace;
+
+const NAMES: [&str; 16] = [
+    "alice",
+    "a_b-c.9",
+    "",
+    "\0",
+    "nul\0inside",
+    "b o b",
+    " padded ",
+    "say \"hi\"",
+    "back\\slash",
+    "tab\tnew\nline\r",
+    "it's",
+    "a,b",
+    "f(x)",
+    "zoë",
+    "zero\u{200b}width",
+    "del\u{7f}",
+];
+
+fn ops(name: &str) -> Vec<Op> {
+    vec![
+        Op::Deposit {
+            account: name.to_string(),
+            amount: 0,
+        },
+        Op::Withdraw {
+            account: name.to_string(),
+            amount: u64::MAX,
+        },
+        Op::Transfer {
+            from: name.to_string(),
+            to: "alice".to_string(),
+            amount: 5,
+        },
+        Op::Transfer {
+            from: "alice".to_string(),
+            to: name.to_string(),
+            amount: 7,
+        },
+    ]
+}
+
+#[test]
+fn ops_round_trip_through_display() {
+    for name in NAMES {
+        for op in ops(name) {
+            let text = op.to_string();
+            assert!(!text.contains('\n'), "{:?} spans lines", text);
+            assert_eq!(text.parse::<Op>(), Ok(op), "from {:?}", text);
+        }
+    }
+}
+
+#[test]
+fn only_unambiguous_names_are_left_bare() {
+    assert_eq!(AccountName("alice").to_string(), "alice");
+    assert_eq!(AccountName("a_b-c.9").to_string(), "a_b-c.9");
+    assert_eq!(AccountName("").to_string(), "\"\"");
+    assert_eq!(AccountName("\0").to_string(), "\"\\0\"");
+    assert_eq!(AccountName("b o b").to_string(), "\"b o b\"");
+    assert_eq!(AccountName("say \"hi\"").to_string(), "\"say \\\"hi\\\"\"");
+    assert_eq!(
+        "deposit(\"\", 3)".parse::<Op>(),
+        Ok(Op::Deposit {
+            account: String::new(),
+            amount: 3
+        })
+    );
+}
+
+#[test]
+fn escapes_parse_to_the_characters_they_name() {
+    let parsed = r#"transfer("\u{1f980}\t\\", "\'\0", 1)"#.parse::<Op>();
+    assert_eq!(
+        parsed,
+        Ok(Op::Transfer {
+            from: "🦀\t\\".to_string(),
+            to: "'\0".to_string(),
+            amount: 1,
+        })
+    );
+}
+
+#[test]
+fn malformed_ops_are_rejected() {
+    for text in [
+        "deposit(\"alice, 5)",
+        "deposit(\"a\\q\", 5)",
+        "deposit(\"\\u{110000}\", 5)",
+        "deposit(\"a\" b, 5)",
+        "deposit(alice)",
+        "deposit(alice, -1)",
+        "deposit(alice, 5",
+        "mint(alice, 5)",
+    ] {
+        assert!(text.parse::<Op>().is_err(), "{} parsed", text);
+    }
+}
+
+#[test]
+fn trace_files_round_trip() {
+    let trace = Trace {
+        initial: NAMES
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.to_string(), index as u64 * 1000))
+            .chain([("max".to_string(), u64::MAX)])
+            .collect(),
+        ops: NAMES.iter().flat_map(|name| ops(name)).collect(),
+    };
+    let comments = vec!["seed=1 case=2 step=3".to_string(), String::new()];
+    let text = trace::format(&trace, &comments);
+    assert!(text.starts_with("# seed=1 case=2 step=3\n#"));
+    assert_eq!(trace::parse(&text), Ok(trace));
+
+    let empty = Trace {
+        initial: Vec::new(),
+        ops: Vec::new(),
+    };
+    assert_eq!(trace::parse(&trace::format(&empty, &[])), Ok(empty));
+}
+
+#[test]
+fn trace_files_need_a_known_version_first() {
+    assert!(trace::parse("deposit(alice, 5)\n").is_err());
+    assert!(trace::parse("version 2\ndeposit(alice, 5)\n").is_err());
+    assert!(trace::parse("version 1\ndeposit(alice, 5)\ninitial(bob, 1)\n").is_err());
+    assert_eq!(
+        trace::parse("\n# comment\nversion 1\n\n  deposit(alice, 5)  \n"),
+        Ok(Trace {
+            initial: Vec::new(),
+            ops: vec![Op::Deposit {
+                account: "alice".to_string(),
+                amount: 5
+            }],
+        })
+    );
+}
@@ -0,0 +1,229 @@
+// Checks failure signatures: their text form, how a failure maps to one, and
+// how `dedup` buckets failures by them.
+
+use verified_ledger::fuzz::{FuzzFailure, HarnessFault, ImplOutcome, StepRecord};
+use verified_ledger::op::{ApplyError, Op};
+use verified_ledger::triage::{self, Gate, Signature};
+
+const OPS: [&str; 3] = ["deposit", "withdraw", "transfer"];
+const MODEL_VERDICTS: [&str; 3] = ["ok", "insufficient-funds", "overflow"];
+const IMPL_VERDICTS: [&str; 4] = ["ok", "insufficient-funds", "overflow", "panic"];
+const MISMATCHES: [&str; 4] = ["result", "state", "result+state", "harness-fault"];
+
+#[test]
+fn every_signature_round_trips_through_display() {
+    for op in OPS {
+        for model in MODEL_VERDICTS {
+            for implementation in IMPL_VERDICTS {
+                for mismatch in MISMATCHES {
+                    let signature = Signature {
+                        op,
+                        model,
+                        implementation,
+                        mismatch,
+                    };
+                    let text = signature.to_string();
+                    assert_eq!(text.parse::<Signature>(), Ok(signature), "from {}", text);
+                }
+            }
+        }
+    }
+    assert_eq!(
+        "  withdraw   model=ok impl=insufficient-funds\tmismatch=result+state ".parse(),
+        Ok(Signature {
+            op: "withdraw",
+            model: "ok",
+            implementation: "insufficient-funds",
+            mismatch: "result+state",
+        })
+    );
+}
+
+#[test]
+fn malformed_signatures_are_rejected() {
+    for text in [
+        "",
+        "withdraw",
+        "mint model=ok impl=ok mismatch=state",
+        "withdraw model=panic impl=ok mismatch=state",
+        "withdraw impl=ok model=ok mismatch=state",
+        "withdraw model=ok impl=ok mismatch=both",
+        "withdraw model=ok impl=ok mismatch=state extra",
+        "withdraw model=ok impl=ok mismatch-state",
+        "withdraw model= ok impl=ok mismatch=state",
+    ] {
+        assert!(text.parse::<Signature>().is_err(), "{:?} parsed", text);
+    }
+}
+
+fn withdraw(account: &str, amount: u64) -> Op {
+    Op::Withdraw {
+        account: account.to_string(),
+        amount,
+    }
+}
+
+fn insufficient(account: &str, balance: u64, amount: u64) -> Result<(), ApplyError> {
+    Err(ApplyError::InsufficientFunds {
+        account: account.to_string(),
+        balance,
+        amount,
+    })
+}
+
+/// A failure on `withdraw(a, 5)` from a balance of 5, after `steps - 1`
+/// agreeing steps. `impl_snapshot` is what the implementation held after it.
+fn failure(
+    steps: usize,
+    impl_outcome: ImplOutcome,
+    impl_snapshot: Vec<(String, u64)>,
+    case_index: u64,
+) -> FuzzFailure {
+    let op = withdraw("a", 5);
+    let before = vec![("a".to_string(), 5)];
+    let mut history: Vec<StepRecord> = (1..steps)
+        .map(|_| StepRecord {
+            op: withdraw("a", 6),
+            model_result: insufficient("a", 5, 6),
+            impl_outcome: ImplOutcome::Returned(insufficient("a", 5, 6)),
+            model_snapshot: before.clone(),
+            impl_snapshot: before.clone(),
+        })
+        .collect();
+    history.push(StepRecord {
+        op: op.clone(),
+        model_result: Ok(()),
+        impl_outcome: impl_outcome.clone(),
+        model_snapshot: Vec::new(),
+        impl_snapshot,
+    });
+    FuzzFailure {
+        seed: 1,
+        case_index,
+        step_index: steps - 1,
+        op,
+        model_result: Ok(()),
+        impl_outcome,
+        model_pre_snapshot: before.clone(),
+        impl_pre_snapshot: before.clone(),
+        initial: before,
+        history,
+        minimized: None,
+        replay_args: String::new(),
+        harness_fault: None,
+    }
+}
+
+fn refused() -> ImplOutcome {
+    ImplOutcome::Returned(insufficient("a", 5, 5))
+}
+
+fn untouched() -> Vec<(String, u64)> {
+    vec![("a".to_string(), 5)]
+}
+
+#[test]
+fn signatures_say_which_side_disagreed() {
+    let signature = |failure: &FuzzFailure| Signature::of(failure).to_string();
+    assert_eq!(
+        signature(&failure(1, refused(), untouched(), 0)),
+        "withdraw model=ok impl=insufficient-funds mismatch=result+state"
+    );
+    assert_eq!(
+        signature(&failure(1, refused(), Vec::new(), 0)),
+        "withdraw model=ok impl=insufficient-funds mismatch=result"
+    );
+    assert_eq!(
+        signature(&failure(1, ImplOutcome::Returned(Ok(())), untouched(), 0)),
+        "withdraw model=ok impl=ok mismatch=state"
+    );
+    assert_eq!(
+        signature(&failure(
+            1,
+            ImplOutcome::Panicked("boom".to_string()),
+            untouched(),
+            0
+        )),
+        "withdraw model=ok impl=panic mismatch=result+state"
+    );
+
+    let mut fault = failure(1, ImplOutcome::Returned(Ok(())), Vec::new(), 0);
+    fault.harness_fault = Some(HarnessFault {
+        reference_result: insufficient("a", 5, 5),
+        reference_snapshot: untouched(),
+    });
+    assert_eq!(
+        signature(&fault),
+        "withdraw model=ok impl=ok mismatch=harness-fault"
+    );
+}
+
+#[test]
+fn dedup_groups_by_signature_and_keeps_the_shortest_history() {
+    let failures = [
+        failure(4, refused(), untouched(), 0),
+        failure(3, ImplOutcome::Returned(Ok(())), untouched(), 1),
+        failure(2, refused(), untouched(), 2),
+        failure(5, refused(), untouched(), 3),
+        failure(1, ImplOutcome::Returned(Ok(())), untouched(), 4),
+        failure(1, ImplOutcome::Returned(Ok(())), untouched(), 5),
+    ];
+    let buckets = triage::dedup(&failures);
+
+    let summary: Vec<(String, usize, u64)> = buckets
+        .iter()
+        .map(|bucket| {
+            (
+                bucket.signature.to_string(),
+                bucket.count,
+                bucket.representative.case_index,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "withdraw model=ok impl=insufficient-funds mismatch=result+state".to_string(),
+                3,
+                2
+            ),
+            ("withdraw model=ok impl=ok mismatch=state".to_string(), 3, 4),
+        ]
+    );
+    for bucket in &buckets {
+        assert_eq!(Signature::of(&bucket.representative), bucket.signature);
+    }
+    assert!(triage::dedup(&[]).is_empty());
+}
+
+#[test]
+fn expected_signatures_a_run_misses_are_not_hit_rather_than_fixed() {
+    let failures = [failure(1, refused(), untouched(), 0)];
+    let buckets = triage::dedup(&failures);
+    let expected: Vec<Signature> = [
+        "withdraw model=ok impl=insufficient-funds mismatch=result+state",
+        "transfer model=ok impl=ok mismatch=state",
+    ]
+    .iter()
+    .map(|text| text.parse().expect("signature parses"))
+    .collect();
+
+    let gate = Gate::check(&buckets, &expected);
+    assert!(gate.passed());
+    assert_eq!(gate.known.len(), 1);
+    assert!(gate.new.is_empty());
+    assert_eq!(gate.not_hit, [&expected[1]]);
+    let text = gate.to_string();
+    assert!(text.contains("1 known, 0 new, 1 not hit"), "{}", text);
+    assert!(
+        text.contains("not hit: transfer model=ok impl=ok mismatch=state (not hit in this run)"),
+        "{}",
+        text
+    );
+    assert!(!text.contains("fixed"), "{}", text);
+
+    let gate = Gate::check(&buckets, &expected[1..]);
+    assert!(!gate.passed());
+    assert_eq!(gate.new.len(), 1);
+}